@@ -0,0 +1,273 @@
+//! Credentials for fetching from remotes that need authentication.
+//!
+//! libgit2 calls the credentials callback again each time a credential is
+//! rejected, so `Credentials` remembers what it has offered and moves on to
+//! the next source: the SSH agent, then key files, then git's credential
+//! helpers, and finally a prompt when there is a terminal to ask on.
+//! `url.<base>.insteadOf` rewrites are applied by libgit2 when the remote is
+//! loaded, so the URL seen here is the one actually fetched from.
+
+use std::env;
+use std::io::{self, IsTerminal, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+
+use git2::{Config, Cred, CredentialType, RemoteCallbacks, Repository};
+
+/// Key files tried when `trees.sshKey` is not set, relative to `~/.ssh`
+const DEFAULT_KEYS: [&str; 3] = ["id_ed25519", "id_ecdsa", "id_rsa"];
+
+/// Prompts from parallel fetches would garble each other, so they take turns
+static PROMPT_LOCK: Mutex<()> = Mutex::new(());
+
+pub struct Credentials {
+    config: Option<Config>,
+    use_agent: bool,
+    interactive: bool,
+    tried_agent: bool,
+    tried_keys: usize,
+    tried_helper: bool,
+    tried_prompt: bool,
+}
+
+impl Credentials {
+    /// Credentials for fetching from remotes of `repo`, using its config for
+    /// key files and credential helpers
+    pub fn new(repo: &Repository) -> Self {
+        Credentials {
+            config: repo.config().ok(),
+            use_agent: env::var_os("SSH_AUTH_SOCK").is_some(),
+            interactive: io::stdin().is_terminal() && io::stderr().is_terminal(),
+            tried_agent: false,
+            tried_keys: 0,
+            tried_helper: false,
+            tried_prompt: false,
+        }
+    }
+
+    /// The next credential to offer for `url`
+    pub fn next(
+        &mut self,
+        url: &str,
+        username_from_url: Option<&str>,
+        allowed: CredentialType,
+    ) -> Result<Cred, git2::Error> {
+        // SSH asks for the user name on its own first when the URL has none
+        if allowed.contains(CredentialType::USERNAME) {
+            return Cred::username(username_from_url.unwrap_or("git"));
+        }
+
+        if allowed.contains(CredentialType::SSH_KEY) {
+            let username = username_from_url.unwrap_or("git");
+            if self.use_agent && !self.tried_agent {
+                self.tried_agent = true;
+                return Cred::ssh_key_from_agent(username);
+            }
+            let keys = self.key_files();
+            if let Some(key) = keys.get(self.tried_keys) {
+                self.tried_keys += 1;
+                let public = key.with_extension("pub");
+                let public = public.exists().then_some(public);
+                return Cred::ssh_key(username, public.as_deref(), key, None);
+            }
+        }
+
+        if allowed.contains(CredentialType::USER_PASS_PLAINTEXT) {
+            if !self.tried_helper
+                && let Some(config) = &self.config
+            {
+                self.tried_helper = true;
+                if let Ok(cred) = Cred::credential_helper(config, url, username_from_url) {
+                    return Ok(cred);
+                }
+            }
+            if self.interactive && !self.tried_prompt {
+                self.tried_prompt = true;
+                return prompt(url, username_from_url);
+            }
+        }
+
+        Err(git2::Error::from_str(&format!(
+            "authentication failed for '{url}' after trying {}",
+            self.tried_sources().join(", ")
+        )))
+    }
+
+    /// Key files from `trees.sshKey`, or the usual ones in `~/.ssh`, that exist
+    fn key_files(&self) -> Vec<PathBuf> {
+        let mut configured = Vec::new();
+        if let Some(config) = &self.config
+            && let Ok(mut entries) = config.multivar("trees.sshKey", None)
+        {
+            while let Some(Ok(entry)) = entries.next() {
+                if let Some(value) = entry.value() {
+                    configured.push(expand_home(value));
+                }
+            }
+        }
+        if configured.is_empty()
+            && let Some(home) = env::var_os("HOME")
+        {
+            let ssh_dir = PathBuf::from(home).join(".ssh");
+            configured.extend(DEFAULT_KEYS.iter().map(|key| ssh_dir.join(key)));
+        }
+        configured.retain(|key| key.exists());
+        configured
+    }
+
+    fn tried_sources(&self) -> Vec<&'static str> {
+        let mut tried = Vec::new();
+        if self.tried_agent {
+            tried.push("the SSH agent");
+        }
+        if self.tried_keys > 0 {
+            tried.push("SSH key files");
+        }
+        if self.tried_helper {
+            tried.push("credential helpers");
+        }
+        if self.tried_prompt {
+            tried.push("a password prompt");
+        }
+        if tried.is_empty() {
+            tried.push("no credentials");
+        }
+        tried
+    }
+}
+
+/// Remote callbacks that answer credential requests for remotes of `repo`
+pub fn remote_callbacks<'a>(repo: &Repository) -> RemoteCallbacks<'a> {
+    let mut credentials = Credentials::new(repo);
+    let mut callbacks = RemoteCallbacks::new();
+    callbacks.credentials(move |url, username_from_url, allowed| {
+        credentials.next(url, username_from_url, allowed)
+    });
+    callbacks
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), env::var_os("HOME")) {
+        (Some(rest), Some(home)) => PathBuf::from(home).join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// Ask for a user name and password on the terminal
+fn prompt(url: &str, username_from_url: Option<&str>) -> Result<Cred, git2::Error> {
+    let _lock = PROMPT_LOCK.lock().unwrap();
+    let read = |question: &str, hidden: bool| -> io::Result<String> {
+        eprint!("{question}");
+        io::stderr().flush()?;
+        let echo = |flag: &str| {
+            let _ = Command::new("stty")
+                .arg(flag)
+                .stdin(Stdio::inherit())
+                .status();
+        };
+        if hidden {
+            echo("-echo");
+        }
+        let mut input = String::new();
+        let result = io::stdin().read_line(&mut input);
+        if hidden {
+            echo("echo");
+            eprintln!();
+        }
+        result.map(|_| input.trim_end_matches(['\r', '\n']).to_string())
+    };
+
+    let prompt_error = |e: io::Error| git2::Error::from_str(&format!("failed to prompt: {e}"));
+    let username = match username_from_url {
+        Some(username) => username.to_string(),
+        None => read(&format!("Username for '{url}': "), false).map_err(prompt_error)?,
+    };
+    let password = read(&format!("Password for '{url}': "), true).map_err(prompt_error)?;
+    Cred::userpass_plaintext(&username, &password)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    fn credentials(repo: &Repository) -> Credentials {
+        Credentials {
+            use_agent: false,
+            interactive: false,
+            ..Credentials::new(repo)
+        }
+    }
+
+    #[test]
+    fn test_credential_helper() {
+        let dir = tempdir().unwrap();
+        let repo = Repository::init(dir.path().join("repo")).unwrap();
+        let marker = dir.path().join("helper-ran");
+        let helper = dir.path().join("helper.sh");
+        fs::write(
+            &helper,
+            format!(
+                "#!/bin/sh\ntouch '{}'\necho username=alice\necho password=secret\n",
+                marker.display()
+            ),
+        )
+        .unwrap();
+        let mut config = repo.config().unwrap();
+        config
+            .set_str("credential.helper", &format!("!sh {}", helper.display()))
+            .unwrap();
+
+        let mut credentials = credentials(&repo);
+        let url = "https://example.com/project.git";
+        let cred = credentials
+            .next(url, None, CredentialType::USER_PASS_PLAINTEXT)
+            .unwrap();
+        assert_eq!(cred.credtype(), CredentialType::USER_PASS_PLAINTEXT.bits());
+        assert!(marker.exists());
+
+        // Once the helper's answer is rejected there is nothing left to try
+        let err = credentials
+            .next(url, None, CredentialType::USER_PASS_PLAINTEXT)
+            .err()
+            .unwrap();
+        assert!(err.message().contains("credential helpers"));
+    }
+
+    #[test]
+    fn test_ssh_key_files_in_order() {
+        let dir = tempdir().unwrap();
+        let repo = Repository::init(dir.path().join("repo")).unwrap();
+        let mut config = repo.config().unwrap();
+        for name in ["first", "missing", "second"] {
+            let key = dir.path().join(name);
+            if name != "missing" {
+                fs::write(&key, "not a real key").unwrap();
+            }
+            config
+                .set_multivar("trees.sshKey", "^$", key.to_str().unwrap())
+                .unwrap();
+        }
+
+        let mut credentials = credentials(&repo);
+        let url = "ssh://git@example.com/project.git";
+        let username = credentials
+            .next(url, None, CredentialType::USERNAME)
+            .unwrap();
+        assert_eq!(username.credtype(), CredentialType::USERNAME.bits());
+        for _ in 0..2 {
+            let cred = credentials
+                .next(url, Some("git"), CredentialType::SSH_KEY)
+                .unwrap();
+            assert_eq!(cred.credtype(), CredentialType::SSH_KEY.bits());
+        }
+        assert_eq!(credentials.tried_keys, 2);
+        let err = credentials
+            .next(url, Some("git"), CredentialType::SSH_KEY)
+            .err()
+            .unwrap();
+        assert!(err.message().contains("SSH key files"));
+    }
+}
@@ -0,0 +1,110 @@
+//! Cache of worktree dirty checks.
+//!
+//! A full status scan with untracked files is the slowest part of listing
+//! worktrees, so results are kept in `trees-cache.json` in the repository's
+//! common git directory. An entry is reused while the modification times of
+//! the worktree's index and HEAD are unchanged. Edits to tracked files change
+//! neither, so entries only serve as a first guess for the worktree picker.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+const CACHE_FILE: &str = "trees-cache.json";
+
+/// Modification times, in nanoseconds since the epoch, that an entry is valid for
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Stamp {
+    index: Option<u128>,
+    head: Option<u128>,
+}
+
+impl Stamp {
+    /// Read the stamp of the worktree whose private git directory is `git_dir`
+    pub fn read(git_dir: &Path) -> Self {
+        let mtime = |file: &str| {
+            fs::metadata(git_dir.join(file))
+                .and_then(|metadata| metadata.modified())
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map(|time| time.as_nanos())
+        };
+        Stamp {
+            index: mtime("index"),
+            head: mtime("HEAD"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    stamp: Stamp,
+    is_dirty: bool,
+}
+
+#[derive(Default)]
+pub struct DirtyCache {
+    file: Option<PathBuf>,
+    entries: HashMap<String, Entry>,
+    changed: bool,
+}
+
+impl DirtyCache {
+    /// Load the cache stored in `common_dir`. A missing or unreadable cache
+    /// file starts out empty.
+    pub fn load(common_dir: &Path) -> Self {
+        let file = common_dir.join(CACHE_FILE);
+        let entries = fs::read(&file)
+            .ok()
+            .and_then(|contents| serde_json::from_slice(&contents).ok())
+            .unwrap_or_default();
+        DirtyCache {
+            file: Some(file),
+            entries,
+            changed: false,
+        }
+    }
+
+    /// Cached result for the worktree at `path`, if it is still valid for `stamp`
+    pub fn get(&self, path: &str, stamp: Stamp) -> Option<bool> {
+        self.entries
+            .get(path)
+            .filter(|entry| entry.stamp == stamp)
+            .map(|entry| entry.is_dirty)
+    }
+
+    pub fn insert(&mut self, path: &str, stamp: Stamp, is_dirty: bool) {
+        self.entries
+            .insert(path.to_string(), Entry { stamp, is_dirty });
+        self.changed = true;
+    }
+
+    /// Drop entries for worktrees that no longer exist
+    pub fn retain_paths(&mut self, paths: &[&str]) {
+        let before = self.entries.len();
+        self.entries
+            .retain(|path, _| paths.contains(&path.as_str()));
+        self.changed |= self.entries.len() != before;
+    }
+
+    /// Write the cache back if anything changed since it was loaded or last
+    /// saved. The file is replaced atomically so concurrent runs never read a
+    /// partial cache.
+    pub fn save(&mut self) -> Result<()> {
+        let Some(file) = &self.file else {
+            return Ok(());
+        };
+        if !self.changed {
+            return Ok(());
+        }
+        let temp = file.with_extension(format!("json.{}", std::process::id()));
+        fs::write(&temp, serde_json::to_vec(&self.entries)?)
+            .with_context(|| format!("Failed to write {}", temp.display()))?;
+        fs::rename(&temp, file).with_context(|| format!("Failed to write {}", file.display()))?;
+        self.changed = false;
+        Ok(())
+    }
+}
@@ -1,4 +1,5 @@
-use clap::{Args, Parser, Subcommand};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use std::num::NonZeroUsize;
 
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
@@ -24,23 +25,243 @@ pub struct Config {
     /// Output only directory paths (for shell integration)
     #[arg(long, global = true)]
     pub dir_only: bool,
+
+    /// Only print errors and warnings
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    pub quiet: bool,
+
+    /// Print more detail about what is happening
+    #[arg(short, long, global = true)]
+    pub verbose: bool,
 }
 
 #[derive(Subcommand)]
 pub enum Commands {
     /// List worktrees
-    List,
+    List {
+        /// Output format
+        #[arg(long, value_enum, default_value_t = ListFormat::Text)]
+        format: ListFormat,
+    },
+    /// Show branch, upstream and change status of all worktrees
+    Status,
     /// Add a new worktree
-    Add,
-    /// Remove a worktree
-    Rm,
+    Add {
+        /// Branch to check out (skips interactive selection)
+        branch: Option<String>,
+
+        /// Revision to start a new branch from
+        #[arg(long, value_name = "REV", requires = "branch")]
+        base: Option<String>,
+
+        /// Remote branch to track, e.g. origin/main
+        #[arg(long, value_name = "REMOTE/BRANCH", requires = "branch")]
+        track: Option<String>,
+
+        /// Directory to create the worktree in
+        #[arg(long, value_name = "DIR", requires = "branch")]
+        dir: Option<String>,
+    },
+    /// Clone a repository as a bare repo with a worktree per branch
+    Clone {
+        /// URL or path of the repository to clone
+        url: String,
+
+        /// Directory to create (defaults to the repository name)
+        name: Option<String>,
+    },
+    /// Convert a normal clone to a bare repo with worktrees
+    Convert {
+        /// Print what would be done without changing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Remove worktrees
+    Rm {
+        /// Worktrees to remove, by name, branch or path (skips interactive
+        /// selection)
+        targets: Vec<String>,
+
+        /// Remove worktrees with uncommitted changes or unpushed commits
+        #[arg(short, long)]
+        force: bool,
+
+        /// Don't ask for confirmation
+        #[arg(short, long)]
+        yes: bool,
+
+        /// Unlock and remove locked worktrees too
+        #[arg(long)]
+        unlock: bool,
+
+        /// Delete each worktree's branch too (default: `trees.deleteBranch`)
+        #[arg(long, conflicts_with = "keep_branch")]
+        delete_branch: bool,
+
+        /// Keep the branches even if `trees.deleteBranch` is set
+        #[arg(long)]
+        keep_branch: bool,
+
+        /// Delete the branches on their remote too (implies --delete-branch)
+        #[arg(long, conflicts_with = "keep_branch")]
+        delete_remote: bool,
+    },
+    /// Clean up worktrees whose directory is gone
+    Prune,
+    /// Find stale worktrees and pick which to remove
+    Clean {
+        /// Offer worktrees with no commits or checkouts in this many days
+        #[arg(long, default_value_t = 30)]
+        days: u64,
+
+        /// Remove every stale worktree without asking
+        #[arg(short, long)]
+        yes: bool,
+
+        /// Remove stale worktrees with uncommitted changes or unpushed commits
+        #[arg(short, long)]
+        force: bool,
+
+        /// Only list the stale worktrees
+        #[arg(long, conflicts_with = "yes")]
+        dry_run: bool,
+
+        /// Delete each worktree's branch too (default: `trees.deleteBranch`)
+        #[arg(long, conflicts_with = "keep_branch")]
+        delete_branch: bool,
+
+        /// Keep the branches even if `trees.deleteBranch` is set
+        #[arg(long)]
+        keep_branch: bool,
+    },
     /// Merge two worktrees
-    Merge,
+    Merge {
+        /// How to bring the source branch into the target
+        #[arg(short, long, value_enum, default_value_t = MergeStrategy::Merge)]
+        strategy: MergeStrategy,
+
+        /// Message for the merge or squash commit
+        #[arg(short, long)]
+        message: Option<String>,
+
+        /// Report whether the merge would fast-forward, apply cleanly or
+        /// conflict, without changing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Merge a worktree into the default branch, push it and clean up
+    Land {
+        /// Worktree to land, by name, branch or path (default: the current one)
+        #[arg(conflicts_with_all = ["resume", "abort"])]
+        worktree: Option<String>,
+
+        /// How to bring the worktree's branch into the default branch
+        #[arg(short, long, value_enum, default_value_t = MergeStrategy::Rebase)]
+        strategy: MergeStrategy,
+
+        /// Delete the branch on its remote too
+        #[arg(long)]
+        delete_remote: bool,
+
+        /// Continue a landing that stopped at a failed step
+        #[arg(long = "continue", conflicts_with = "abort")]
+        resume: bool,
+
+        /// Forget a landing that stopped at a failed step
+        #[arg(long)]
+        abort: bool,
+
+        /// Number of remotes to fetch and worktrees to update at once
+        /// (default: number of CPUs)
+        #[arg(short, long)]
+        jobs: Option<NonZeroUsize>,
+    },
+    /// Predict which worktrees' branches would conflict when merged
+    Conflicts {
+        /// Check each worktree against the default branch instead of every
+        /// pair of worktrees
+        #[arg(long)]
+        default_branch: bool,
+    },
+    /// Fetch all remotes and report which remote branches changed
+    Fetch {
+        /// Remove remote branches that were deleted on the remote
+        #[arg(long)]
+        prune: bool,
+
+        /// Number of remotes to fetch at once (defaults to the number of CPUs)
+        #[arg(short, long, value_name = "N")]
+        jobs: Option<NonZeroUsize>,
+    },
     /// Pull updates for all worktrees
-    Pull,
+    Pull {
+        /// Rebase branches that have diverged from their upstream
+        #[arg(long)]
+        rebase: bool,
+
+        /// Stash uncommitted changes before updating and re-apply them after
+        #[arg(long)]
+        autostash: bool,
+
+        /// Number of remotes and worktrees to update at once (defaults to
+        /// the number of CPUs)
+        #[arg(short, long, value_name = "N")]
+        jobs: Option<NonZeroUsize>,
+    },
+    /// Rebase every worktree's branch onto the updated default branch
+    Sync {
+        /// Merge the default branch in instead of rebasing onto it
+        #[arg(long)]
+        merge: bool,
+
+        /// Stash uncommitted changes before updating and re-apply them after
+        #[arg(long)]
+        autostash: bool,
+
+        /// Number of remotes and worktrees to update at once (defaults to
+        /// the number of CPUs)
+        #[arg(short, long, value_name = "N")]
+        jobs: Option<NonZeroUsize>,
+    },
     /// Show shell integration script
     Shell {
         /// Shell type (zsh, bash, fish)
         shell: String,
     },
 }
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum ListFormat {
+    /// Human-readable, like `git worktree list`
+    Text,
+    /// Versioned JSON document
+    Json,
+    /// Versioned line-based records, like `git worktree list --porcelain`
+    Porcelain,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum MergeStrategy {
+    /// Fast-forward when possible, otherwise create a merge commit
+    Merge,
+    /// Only fast-forward, failing if the branches have diverged
+    FfOnly,
+    /// Always create a merge commit
+    NoFf,
+    /// Create one commit on the target with all of the source's changes
+    Squash,
+    /// Rebase the source onto the target, then fast-forward the target
+    Rebase,
+}
+
+impl MergeStrategy {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            MergeStrategy::Merge => "merge",
+            MergeStrategy::FfOnly => "ff-only",
+            MergeStrategy::NoFf => "no-ff",
+            MergeStrategy::Squash => "squash",
+            MergeStrategy::Rebase => "rebase",
+        }
+    }
+}
@@ -1,16 +1,69 @@
-use std::{fs, path::Path, process::Command};
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::{Mutex, mpsc},
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
 use anyhow::{Context, Result};
 use git2::Repository;
 
+use crate::auth::remote_callbacks;
+use crate::cache::{DirtyCache, Stamp};
+use crate::cli::MergeStrategy;
+use crate::progress::{Progress, describe_transfer};
+use crate::report::{info, verbose, warning};
+
 pub struct WorktreeInfo {
     pub name: String,
     pub path: String,
     pub branch: String,
     pub is_dirty: bool,
+    pub state: WorktreeState,
+}
+
+/// State of a worktree as recorded in git's worktree metadata
+#[derive(Clone, Debug, PartialEq)]
+pub enum WorktreeState {
+    /// The worktree directory exists and is usable
+    Active,
+    /// Locked against pruning, with the optional lock reason
+    Locked(Option<String>),
+    /// The worktree directory is gone and the entry can be pruned
+    Prunable,
+    /// The worktree directory is gone but the entry is locked, so it is kept
+    Missing,
+}
+
+impl WorktreeState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WorktreeState::Active => "active",
+            WorktreeState::Locked(_) => "locked",
+            WorktreeState::Prunable => "prunable",
+            WorktreeState::Missing => "missing",
+        }
+    }
+
+    /// Whether the worktree directory is present on disk
+    pub fn is_present(&self) -> bool {
+        matches!(self, WorktreeState::Active | WorktreeState::Locked(_))
+    }
 }
 
 pub fn get_worktrees(repo_path: &str) -> Result<Vec<WorktreeInfo>> {
+    let mut worktrees = list_worktrees(repo_path)?;
+    for (index, is_dirty) in check_dirty(repo_path, &worktrees) {
+        worktrees[index].is_dirty = is_dirty;
+    }
+    Ok(worktrees)
+}
+
+/// List worktrees without checking them for changes; `is_dirty` is always
+/// false. Use `check_dirty` to fill it in.
+pub fn list_worktrees(repo_path: &str) -> Result<Vec<WorktreeInfo>> {
     // First, find the main repository path
     let main_repo_path = find_main_repo_path(repo_path)?;
 
@@ -18,102 +71,233 @@ pub fn get_worktrees(repo_path: &str) -> Result<Vec<WorktreeInfo>> {
     let worktrees = repo.worktrees().context("failed to get worktrees")?;
     let mut worktree_infos = Vec::new();
 
-    // Add the main repository as the first worktree
-    let main_path = fs::canonicalize(&main_repo_path)
-        .with_context(|| format!("Failed to canonicalize path: {}", main_repo_path))?
-        .to_string_lossy()
-        .to_string();
-    let main_branch =
-        get_worktree_branch(&main_repo_path).unwrap_or_else(|_| "unknown".to_string());
-    let main_dirty = is_worktree_dirty(&main_repo_path).unwrap_or(false);
-    worktree_infos.push(WorktreeInfo {
-        name: "main".to_string(),
-        path: main_path,
-        branch: main_branch,
-        is_dirty: main_dirty,
-    });
+    // Add the main repository as the first worktree, unless it is bare and
+    // has no checkout of its own
+    if !repo.is_bare() {
+        let main_branch =
+            get_worktree_branch(&main_repo_path).unwrap_or_else(|_| "unknown".to_string());
+        worktree_infos.push(WorktreeInfo {
+            name: "main".to_string(),
+            path: main_repo_path.clone(),
+            branch: main_branch,
+            is_dirty: false,
+            state: WorktreeState::Active,
+        });
+    }
 
     // Add additional worktrees
-    for i in 0..worktrees.len() {
-        if let Some(name) = worktrees.get(i) {
-            // Try to get the worktree path
-            let worktree_path = get_worktree_path(&main_repo_path, name);
-            let branch = worktree_path
-                .as_ref()
-                .and_then(|p| get_worktree_branch(p).ok())
-                .unwrap_or_else(|| "unknown".to_string());
-            let is_dirty = worktree_path
-                .as_ref()
-                .map(|p| is_worktree_dirty(p).unwrap_or(false))
-                .unwrap_or(false);
-            worktree_infos.push(WorktreeInfo {
-                name: name.to_string(),
-                path: worktree_path.unwrap_or_else(|| name.to_string()),
-                branch,
-                is_dirty,
-            });
-        }
+    for name in worktrees.iter().flatten() {
+        let worktree = repo
+            .find_worktree(name)
+            .with_context(|| format!("Failed to open worktree '{name}'"))?;
+        let state = get_worktree_state(&worktree);
+        let worktree_path = canonical_worktree_path(&worktree);
+        let branch = if state.is_present() {
+            get_worktree_branch(&worktree_path).unwrap_or_else(|_| "unknown".to_string())
+        } else {
+            "unknown".to_string()
+        };
+        worktree_infos.push(WorktreeInfo {
+            name: name.to_string(),
+            path: worktree_path,
+            branch,
+            is_dirty: false,
+            state,
+        });
     }
     Ok(worktree_infos)
 }
 
-/// Find the main repository path from any worktree or the main repo itself
-fn find_main_repo_path(path: &str) -> Result<String> {
-    let path =
-        fs::canonicalize(path).with_context(|| format!("Failed to canonicalize path: {}", path))?;
-
-    // Check if this is a worktree (has .git file)
-    let git_file_path = path.join(".git");
-    if git_file_path.is_file() {
-        // This is a worktree, read the gitdir from the .git file
-        let git_content = fs::read_to_string(&git_file_path)
-            .with_context(|| format!("Failed to read .git file at {git_file_path:?}"))?;
-        if let Some(gitdir) = git_content.strip_prefix("gitdir: ") {
-            let gitdir_path = gitdir.trim();
-            // The gitdir points to the worktree directory in the main repo
-            // We need to go up two levels to get to the main repo:
-            // .git/worktrees/worktree-name/ -> .git/worktrees/ -> .git/ -> main-repo
-            let worktree_dir = Path::new(gitdir_path)
-                .parent()
-                .ok_or_else(|| anyhow::anyhow!("Invalid gitdir path: {}", gitdir_path))?;
-            let worktrees_dir = worktree_dir.parent().ok_or_else(|| {
-                anyhow::anyhow!("Invalid worktree directory path: {:?}", worktree_dir)
-            })?;
-            let main_repo = worktrees_dir.parent().ok_or_else(|| {
-                anyhow::anyhow!("Invalid worktrees directory path: {:?}", worktrees_dir)
-            })?;
-            Ok(main_repo.to_string_lossy().to_string())
-        } else {
-            // Fallback to the original path
-            Ok(path.to_string_lossy().to_string())
+/// Dirty state of the present worktrees for the picker, as `(index into
+/// worktrees, is_dirty)`. Worktrees whose index and HEAD are unchanged since
+/// they were last checked reuse the dirty cache and only the rest are checked,
+/// so a reused marker misses edits to tracked files made since then.
+/// `check_dirty` checks every worktree.
+pub fn cached_dirty_states(repo_path: &str, worktrees: &[WorktreeInfo]) -> Vec<(usize, bool)> {
+    let mut cache = load_dirty_cache(repo_path);
+    let present = present_worktrees(worktrees);
+    let mut results = Vec::new();
+    let mut unchecked = Vec::new();
+    for (index, path) in &present {
+        let stamp = Repository::open(path)
+            .ok()
+            .map(|repo| Stamp::read(repo.path()));
+        match stamp.and_then(|stamp| cache.get(path, stamp)) {
+            Some(is_dirty) => results.push((*index, is_dirty)),
+            None => unchecked.push((*index, path.clone())),
         }
-    } else {
-        // This is already the main repository
-        Ok(path.to_string_lossy().to_string())
     }
+    results.extend(check_into_cache(&mut cache, unchecked));
+    save_dirty_cache(&mut cache, &present);
+    results
 }
 
-/// Try to get the worktree path by looking for a sibling directory to the repo with the worktree name
-fn get_worktree_path(repo_path: &str, worktree_name: &str) -> Option<String> {
-    let repo_dir = match fs::canonicalize(repo_path) {
-        Ok(path) => path,
-        Err(_) => return None,
-    };
-    let parent = repo_dir.parent()?;
-    let candidate = parent.join(worktree_name);
-    if candidate.exists() {
-        // Return canonicalized (absolute) path
-        match fs::canonicalize(&candidate) {
-            Ok(canonical_path) => Some(canonical_path.to_string_lossy().to_string()),
-            Err(_) => Some(candidate.to_string_lossy().to_string()),
+/// Check the present worktrees for uncommitted changes in parallel, as
+/// `(index into worktrees, is_dirty)`, and record the results in the dirty
+/// cache for `cached_dirty_states`
+pub fn check_dirty(repo_path: &str, worktrees: &[WorktreeInfo]) -> Vec<(usize, bool)> {
+    let mut cache = load_dirty_cache(repo_path);
+    let present = present_worktrees(worktrees);
+    let results = check_into_cache(&mut cache, present.clone());
+    save_dirty_cache(&mut cache, &present);
+    results
+}
+
+fn present_worktrees(worktrees: &[WorktreeInfo]) -> Vec<(usize, String)> {
+    worktrees
+        .iter()
+        .enumerate()
+        .filter(|(_, worktree)| worktree.state.is_present())
+        .map(|(index, worktree)| (index, worktree.path.clone()))
+        .collect()
+}
+
+fn load_dirty_cache(repo_path: &str) -> DirtyCache {
+    find_main_repo_path(repo_path)
+        .ok()
+        .and_then(|path| Repository::open(path).ok())
+        .map(|repo| DirtyCache::load(repo.commondir()))
+        .unwrap_or_default()
+}
+
+/// Check `worktrees` in parallel and record the results in `cache`
+fn check_into_cache(cache: &mut DirtyCache, worktrees: Vec<(usize, String)>) -> Vec<(usize, bool)> {
+    let mut results = Vec::new();
+    // Stamps are read before checking, so a change made during the check
+    // invalidates the entry on the next run
+    run_parallel(
+        worktrees,
+        default_jobs(),
+        |(index, path)| {
+            let stamp = Repository::open(&path)
+                .ok()
+                .map(|repo| Stamp::read(repo.path()));
+            let is_dirty = is_worktree_dirty(&path).unwrap_or(false);
+            (index, path, stamp, is_dirty)
+        },
+        |(index, path, stamp, is_dirty)| {
+            if let Some(stamp) = stamp {
+                cache.insert(&path, stamp, is_dirty);
+            }
+            results.push((index, is_dirty));
+        },
+    );
+    results
+}
+
+/// Number of threads to use for parallel work when not told otherwise
+pub fn default_jobs() -> usize {
+    thread::available_parallelism().map_or(1, |jobs| jobs.get())
+}
+
+/// Run `work` on every item on up to `jobs` threads. Each result is handed to
+/// `on_result` on the calling thread as soon as it is ready.
+fn run_parallel<T: Send, R: Send>(
+    items: Vec<T>,
+    jobs: usize,
+    work: impl Fn(T) -> R + Sync,
+    mut on_result: impl FnMut(R),
+) {
+    let jobs = jobs.clamp(1, items.len().max(1));
+    let queue = Mutex::new(items.into_iter());
+    let (sender, results) = mpsc::channel();
+    thread::scope(|scope| {
+        for _ in 0..jobs {
+            let sender = sender.clone();
+            let (queue, work) = (&queue, &work);
+            scope.spawn(move || {
+                loop {
+                    let next = queue.lock().unwrap().next();
+                    let Some(item) = next else {
+                        break;
+                    };
+                    let _ = sender.send(work(item));
+                }
+            });
         }
+        drop(sender);
+        for result in results {
+            on_result(result);
+        }
+    });
+}
+
+/// Work out the state of a linked worktree from its lock and validity
+fn get_worktree_state(worktree: &git2::Worktree) -> WorktreeState {
+    let exists = worktree.path().exists();
+    match worktree.is_locked() {
+        Ok(git2::WorktreeLockStatus::Locked(reason)) if exists => {
+            // git stores the reason with a trailing newline
+            let reason = reason
+                .map(|reason| reason.trim().to_string())
+                .filter(|reason| !reason.is_empty());
+            WorktreeState::Locked(reason)
+        }
+        Ok(git2::WorktreeLockStatus::Locked(_)) => WorktreeState::Missing,
+        _ if !exists || worktree.validate().is_err() => WorktreeState::Prunable,
+        _ => WorktreeState::Active,
+    }
+}
+
+/// Absolute path of a linked worktree, canonicalized when it still exists
+fn canonical_worktree_path(worktree: &git2::Worktree) -> String {
+    fs::canonicalize(worktree.path())
+        .unwrap_or_else(|_| worktree.path().to_path_buf())
+        .to_string_lossy()
+        .to_string()
+}
+
+/// Open the repository containing `path`, resolving gitdir files and the
+/// `GIT_DIR`/`GIT_COMMON_DIR` environment variables the same way git does
+fn discover_repo(path: &str) -> Result<Repository> {
+    // Like git, an explicit GIT_DIR wins over discovery from the path
+    let repo = if std::env::var_os("GIT_DIR").is_some() {
+        Repository::open_from_env()
     } else {
-        None
+        Repository::open_ext(
+            path,
+            git2::RepositoryOpenFlags::FROM_ENV,
+            &[] as &[&std::ffi::OsStr],
+        )
+    };
+    repo.with_context(|| format!("failed to open git repo at {path}"))
+}
+
+/// Find the main repository path from any worktree or the main repo itself.
+/// For bare repositories this is the bare git directory.
+fn find_main_repo_path(path: &str) -> Result<String> {
+    let repo = discover_repo(path)?;
+    let main_path = match repo.workdir() {
+        // The main checkout knows its own working directory, even when the
+        // git directory lives elsewhere (`--separate-git-dir`)
+        Some(workdir) if !repo.is_worktree() => workdir.to_path_buf(),
+        _ => {
+            let main_repo =
+                Repository::open(repo.commondir()).context("failed to open main git repo")?;
+            main_repo
+                .workdir()
+                .unwrap_or_else(|| main_repo.path())
+                .to_path_buf()
+        }
+    };
+    let main_path = fs::canonicalize(&main_path)
+        .with_context(|| format!("Failed to canonicalize path: {main_path:?}"))?;
+    Ok(main_path.to_string_lossy().to_string())
+}
+
+/// Look up the path of a worktree from git's worktree metadata
+fn get_worktree_path(repo_path: &str, worktree_name: &str) -> Option<String> {
+    let main_repo_path = find_main_repo_path(repo_path).ok()?;
+    let repo = Repository::open(&main_repo_path).ok()?;
+    if worktree_name == "main" && !repo.is_bare() {
+        return Some(main_repo_path);
     }
+    let worktree = repo.find_worktree(worktree_name).ok()?;
+    Some(canonical_worktree_path(&worktree))
 }
 
 pub fn get_branches(repo_path: &str) -> Result<(Vec<String>, Vec<String>)> {
-    let repo = Repository::open(repo_path).context("failed to open git repo")?;
+    let repo = discover_repo(repo_path)?;
     let local_branches = get_branches_type(&repo, git2::BranchType::Local)?;
     let remote_branches = get_branches_type(&repo, git2::BranchType::Remote)?;
     Ok((local_branches, remote_branches))
@@ -126,138 +310,523 @@ fn get_branches_type(repo: &Repository, branch_type: git2::BranchType) -> Result
     let mut branch_names = Vec::new();
     for branch in branches {
         let (branch_ref, _) = branch?;
-        if let Some(name) = branch_ref.name()? {
-            if name != "origin/HEAD" {
-                let clean_name = name.trim_start_matches("origin/");
-                branch_names.push(clean_name.to_string());
-            }
+        // Remote branches keep their `<remote>/` prefix so they can be tracked
+        if let Some(name) = branch_ref.name()?
+            && !(branch_type == git2::BranchType::Remote && name.ends_with("/HEAD"))
+        {
+            branch_names.push(name.to_string());
         }
     }
     Ok(branch_names)
 }
 
-pub fn create_worktree(repo_path: &str, branch_name: &str) -> Result<String> {
-    let path = fs::canonicalize(Path::new(repo_path))?;
+/// Get the local branch name for a remote-tracking branch such as
+/// `origin/feat/x`, using the repository's configured remotes
+pub fn local_branch_for_remote(repo_path: &str, remote_branch: &str) -> Result<String> {
+    let repo = discover_repo(repo_path)?;
+    let remote = repo
+        .branch_remote_name(&format!("refs/remotes/{remote_branch}"))
+        .with_context(|| format!("No remote found for branch '{remote_branch}'"))?;
+    let remote = remote
+        .as_str()
+        .with_context(|| format!("Invalid remote name for branch '{remote_branch}'"))?;
+    remote_branch
+        .strip_prefix(remote)
+        .and_then(|name| name.strip_prefix('/'))
+        .map(str::to_string)
+        .with_context(|| format!("Invalid remote branch name '{remote_branch}'"))
+}
+
+/// Options for creating a worktree non-interactively
+#[derive(Default)]
+pub struct CreateOptions {
+    /// Revision to start a new branch from (defaults to HEAD)
+    pub base: Option<String>,
+    /// Remote-tracking branch to use as the upstream, e.g. `origin/main`
+    pub track: Option<String>,
+    /// Directory for the worktree (defaults to `../<repo>-<branch>`)
+    pub dir: Option<String>,
+}
+
+/// Default name and directory for a new worktree of `branch_name`.
+///
+/// Worktrees of a normal clone go next to the main checkout as
+/// `<repo>-<branch>`. For a bare repository kept in a hidden directory
+/// (`project/.bare`) they go next to it as `project/<branch>`; other bare
+/// repositories (`repo.git`) get `<repo>-<branch>` next to them.
+fn default_worktree_dir(repo_path: &str, branch_name: &str) -> Result<(String, PathBuf)> {
+    let main_repo_path = find_main_repo_path(repo_path)?;
+    let is_bare = Repository::open(&main_repo_path)
+        .context("failed to open git repo")?
+        .is_bare();
+    let path = Path::new(&main_repo_path);
     let parent_dir = path.parent().context("failed to get parent directory")?;
 
     // Get repo name from path
-    let repo_name = path.file_name().and_then(|n| n.to_str()).unwrap_or("repo");
+    let dir_name = path.file_name().and_then(|n| n.to_str()).unwrap_or("repo");
 
-    // Use the format ../$REPO_NAME-$BRANCH_NAME
     // Sanitize branch name by replacing forward slashes with hyphens
     let sanitized_branch_name = branch_name.replace('/', "-");
-    let worktree_name = format!("{repo_name}-{sanitized_branch_name}");
+    let worktree_name = if is_bare && dir_name.starts_with('.') {
+        sanitized_branch_name
+    } else {
+        // Use the format ../$REPO_NAME-$BRANCH_NAME
+        let repo_name = if is_bare {
+            dir_name.strip_suffix(".git").unwrap_or(dir_name)
+        } else {
+            dir_name
+        };
+        format!("{repo_name}-{sanitized_branch_name}")
+    };
     let new_folder = parent_dir.join(&worktree_name);
+    Ok((worktree_name, new_folder))
+}
+
+/// Create a worktree for `branch_name`, returning the path of the new worktree
+pub fn create_worktree(
+    repo_path: &str,
+    branch_name: &str,
+    options: &CreateOptions,
+) -> Result<String> {
+    let (worktree_name, new_folder) = match &options.dir {
+        Some(dir) => {
+            let new_folder = std::path::absolute(dir)
+                .with_context(|| format!("Failed to resolve worktree directory: {dir}"))?;
+            let dir_name = new_folder
+                .file_name()
+                .and_then(|n| n.to_str())
+                .with_context(|| format!("Invalid worktree directory: {dir}"))?;
+            (dir_name.replace('/', "-"), new_folder)
+        }
+        None => default_worktree_dir(repo_path, branch_name)?,
+    };
 
-    println!("Creating worktree with name: '{worktree_name}'");
-    println!("Worktree directory: {new_folder:?}");
+    verbose!("Creating worktree with name: '{worktree_name}'");
+    verbose!("Worktree directory: {new_folder:?}");
 
     // Check if worktree already exists
     if new_folder.exists() {
         return Err(anyhow::anyhow!(
             "Worktree directory '{}' already exists. Please remove it first or use a different branch name.",
-            worktree_name
+            new_folder.display()
         ));
     }
 
     // Open the repository
-    let repo = Repository::open(repo_path).context("failed to open git repo")?;
+    let repo = discover_repo(repo_path)?;
 
     // Check if worktree already exists in the repository
     let existing_worktrees = repo.worktrees().context("failed to get worktrees")?;
-    for i in 0..existing_worktrees.len() {
-        if let Some(name) = existing_worktrees.get(i) {
-            if name == worktree_name {
+    if existing_worktrees
+        .iter()
+        .flatten()
+        .any(|name| name == worktree_name)
+    {
+        return Err(anyhow::anyhow!(
+            "Worktree '{}' already exists in the repository. Please remove it first.",
+            worktree_name
+        ));
+    }
+
+    if let Some(track) = &options.track {
+        repo.find_branch(track, git2::BranchType::Remote)
+            .with_context(|| format!("Remote branch '{track}' not found"))?;
+    }
+
+    // Check if branch exists, create it from the base (or HEAD) if it doesn't
+    let mut created = false;
+    let mut branch = match repo.find_branch(branch_name, git2::BranchType::Local) {
+        Ok(branch) => {
+            if let Some(base) = &options.base {
+                return Err(anyhow::anyhow!(
+                    "Branch '{}' already exists, cannot start it from '{}'",
+                    branch_name,
+                    base
+                ));
+            }
+            info!("Branch '{branch_name}' already exists, using existing branch.");
+            branch
+        }
+        Err(_) => {
+            let commit = match options.base.as_deref().or(options.track.as_deref()) {
+                Some(rev) => repo
+                    .revparse_single(rev)
+                    .and_then(|obj| obj.peel_to_commit())
+                    .with_context(|| format!("Failed to resolve revision '{rev}'"))?,
+                None => {
+                    let head = repo.head().context("failed to get head")?;
+                    head.peel_to_commit().context("failed to peel to commit")?
+                }
+            };
+            let branch = repo
+                .branch(branch_name, &commit, false)
+                .with_context(|| format!("Failed to create branch '{branch_name}'"))?;
+            info!("Branch '{branch_name}' created.");
+            created = true;
+            branch
+        }
+    };
+
+    if let Some(track) = &options.track {
+        // Never silently re-point an existing branch at a different upstream
+        let current_upstream = branch
+            .upstream()
+            .ok()
+            .and_then(|upstream| upstream.name().ok().flatten().map(str::to_string));
+        match current_upstream {
+            Some(upstream) if upstream == *track => {}
+            Some(upstream) if !created => {
                 return Err(anyhow::anyhow!(
-                    "Worktree '{}' already exists in the repository. Please remove it first.",
-                    worktree_name
+                    "Branch '{}' already exists and tracks '{}', not '{}'",
+                    branch_name,
+                    upstream,
+                    track
                 ));
             }
+            _ => {
+                branch.set_upstream(Some(track)).with_context(|| {
+                    format!("Failed to set upstream of '{branch_name}' to '{track}'")
+                })?;
+                info!("Branch '{branch_name}' set up to track '{track}'.");
+            }
         }
     }
 
-    // Check if branch exists, create if it doesn't
-    let branch_exists = repo
-        .find_branch(branch_name, git2::BranchType::Local)
-        .is_ok();
-    if !branch_exists {
-        let head = repo.head().context("failed to get head")?;
-        let commit = head.peel_to_commit().context("failed to peel to commit")?;
-        match repo.branch(branch_name, &commit, false) {
-            Ok(_) => println!("Branch '{branch_name}' created."),
-            Err(e) => {
-                if e.code() == git2::ErrorCode::Exists {
-                    println!("Branch '{branch_name}' already exists, using existing branch.",);
+    // Create the worktree using git2, checking out the branch directly so
+    // libgit2 doesn't create an extra branch named after the worktree
+    let mut opts = git2::WorktreeAddOptions::new();
+    opts.reference(Some(branch.get()));
+    repo.worktree(&worktree_name, &new_folder, Some(&opts))
+        .with_context(|| format!("Failed to create worktree '{worktree_name}'"))?;
+
+    info!("Worktree created at {new_folder:?}");
+    let worktree_path = fs::canonicalize(&new_folder)
+        .with_context(|| format!("Failed to canonicalize path: {new_folder:?}"))?;
+    Ok(worktree_path.to_string_lossy().to_string())
+}
+
+/// Remove a linked worktree: its directory and git's metadata for it. Locked
+/// worktrees are only removed with `unlock`.
+pub fn remove_worktree(repo_path: &str, worktree_name: &str, unlock: bool) -> Result<()> {
+    let main_repo_path = find_main_repo_path(repo_path)?;
+    let repo = Repository::open(&main_repo_path).context("failed to open git repo")?;
+    let worktree = repo
+        .find_worktree(worktree_name)
+        .with_context(|| format!("Could not find worktree '{worktree_name}'"))?;
+    let worktree_path = worktree.path().to_path_buf();
+
+    let mut options = git2::WorktreePruneOptions::new();
+    options.valid(true).locked(unlock).working_tree(true);
+    worktree
+        .prune(Some(&mut options))
+        .with_context(|| format!("Failed to remove worktree: {}", worktree_path.display()))?;
+    // libgit2 leaves the directory behind when it was never a full checkout
+    if worktree_path.exists() {
+        fs::remove_dir_all(&worktree_path)
+            .with_context(|| format!("Failed to delete {}", worktree_path.display()))?;
+    }
+    info!("Worktree '{worktree_name}' removed successfully");
+    Ok(())
+}
+
+/// Remove git's metadata for worktrees whose directory is gone and that
+/// aren't locked, like `git worktree prune`. Returns the pruned worktrees.
+pub fn prune_worktrees(repo_path: &str) -> Result<Vec<WorktreeInfo>> {
+    let main_repo_path = find_main_repo_path(repo_path)?;
+    let repo = Repository::open(&main_repo_path).context("failed to open git repo")?;
+    let mut pruned = Vec::new();
+    for worktree in list_worktrees(repo_path)? {
+        if worktree.state != WorktreeState::Prunable {
+            continue;
+        }
+        repo.find_worktree(&worktree.name)
+            .and_then(|wt| wt.prune(None))
+            .with_context(|| format!("Failed to prune worktree '{}'", worktree.name))?;
+        pruned.push(worktree);
+    }
+    Ok(pruned)
+}
+
+/// Find the worktree `target` refers to, by name, then branch, then path
+pub fn find_worktree_target<'a>(
+    worktrees: &'a [WorktreeInfo],
+    target: &str,
+) -> Option<&'a WorktreeInfo> {
+    let path = fs::canonicalize(target)
+        .ok()
+        .map(|path| path.to_string_lossy().to_string());
+    worktrees
+        .iter()
+        .find(|wt| wt.name == target)
+        .or_else(|| worktrees.iter().find(|wt| wt.branch == target))
+        .or_else(|| worktrees.iter().find(|wt| Some(&wt.path) == path.as_ref()))
+}
+
+/// What would be lost by removing a worktree
+pub struct RemovalRisks {
+    pub is_dirty: bool,
+    /// Commits on HEAD that aren't on any remote-tracking branch
+    pub unpushed: usize,
+}
+
+pub fn get_removal_risks(worktree: &WorktreeInfo) -> Result<RemovalRisks> {
+    if !worktree.state.is_present() {
+        return Ok(RemovalRisks {
+            is_dirty: false,
+            unpushed: 0,
+        });
+    }
+    Ok(RemovalRisks {
+        is_dirty: is_worktree_dirty(&worktree.path)?,
+        unpushed: count_unpushed(&worktree.path)?,
+    })
+}
+
+/// Count commits reachable from HEAD but not from any remote-tracking branch.
+/// Without remote-tracking branches (no remotes, or a mirror-style bare
+/// clone) there is nothing to compare with, so nothing counts.
+fn count_unpushed(worktree_path: &str) -> Result<usize> {
+    let repo = Repository::open(worktree_path).context("failed to open worktree repo")?;
+    if repo.references_glob("refs/remotes/*")?.next().is_none() {
+        return Ok(0);
+    }
+    let Ok(head) = repo.head() else {
+        return Ok(0);
+    };
+    let Some(head) = head.target() else {
+        return Ok(0);
+    };
+    let mut revwalk = repo.revwalk()?;
+    revwalk.push(head)?;
+    revwalk.hide_glob("refs/remotes/*")?;
+    Ok(revwalk.count())
+}
+
+/// Whether `worktree` is the main checkout rather than a linked worktree
+pub fn is_main_worktree(repo_path: &str, worktree: &WorktreeInfo) -> bool {
+    find_main_repo_path(repo_path)
+        .ok()
+        .and_then(|path| Repository::open(&path).ok().map(|repo| (path, repo)))
+        .is_some_and(|(path, repo)| !repo.is_bare() && path == worktree.path)
+}
+
+/// Read a boolean `trees.*` setting from the repository's git config
+pub fn get_config_bool(repo_path: &str, name: &str) -> Option<bool> {
+    let repo = discover_repo(repo_path).ok()?;
+    repo.config().ok()?.get_bool(name).ok()
+}
+
+/// Whether everything on the local branch `branch` is already in its upstream
+/// or in the default branch, so deleting it loses nothing. With
+/// `delete_remote` the upstream is about to go too, so only the default
+/// branch counts.
+pub fn is_branch_merged(repo_path: &str, branch: &str, delete_remote: bool) -> Result<bool> {
+    let repo = discover_repo(repo_path)?;
+    let local = repo
+        .find_branch(branch, git2::BranchType::Local)
+        .with_context(|| format!("Failed to find branch '{branch}'"))?;
+    let Some(tip) = local.get().target() else {
+        return Ok(true);
+    };
+    let upstream = local
+        .upstream()
+        .ok()
+        .and_then(|up| up.get().target())
+        .filter(|_| !delete_remote);
+    let default_tip = default_branch(&repo).map(|(_, tip)| tip);
+    for target in [upstream, default_tip].into_iter().flatten() {
+        if target == tip || repo.graph_descendant_of(target, tip)? {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Name of the default branch, see `default_branch`
+pub fn default_branch_name(repo_path: &str) -> Result<Option<String>> {
+    let repo = discover_repo(repo_path)?;
+    Ok(default_branch(&repo).map(|(name, _)| name))
+}
+
+/// The repository's common git directory, shared by all its worktrees
+pub fn common_dir(repo_path: &str) -> Result<PathBuf> {
+    Ok(discover_repo(repo_path)?.commondir().to_path_buf())
+}
+
+/// Whether the local branch `branch` exists
+pub fn branch_exists(repo_path: &str, branch: &str) -> Result<bool> {
+    let repo = discover_repo(repo_path)?;
+    Ok(repo.find_branch(branch, git2::BranchType::Local).is_ok())
+}
+
+/// Name and tip of the default branch: what `origin/HEAD` points at, or else
+/// a local `main` or `master`
+fn default_branch(repo: &Repository) -> Option<(String, git2::Oid)> {
+    if let Ok(origin_head) = repo.find_reference("refs/remotes/origin/HEAD")
+        && let Some(name) = origin_head
+            .symbolic_target()
+            .and_then(|target| target.strip_prefix("refs/remotes/origin/"))
+        && let Some(tip) = origin_head.resolve().ok().and_then(|r| r.target())
+    {
+        return Some((name.to_string(), tip));
+    }
+    ["main", "master"].iter().find_map(|name| {
+        let tip = repo.refname_to_id(&format!("refs/heads/{name}")).ok()?;
+        Some((name.to_string(), tip))
+    })
+}
+
+/// Delete the local branch `branch`. With `delete_remote`, its upstream branch
+/// is deleted on the remote first.
+pub fn delete_branch(repo_path: &str, branch: &str, delete_remote: bool) -> Result<()> {
+    let repo = discover_repo(repo_path)?;
+    let mut local = repo
+        .find_branch(branch, git2::BranchType::Local)
+        .with_context(|| format!("Failed to find branch '{branch}'"))?;
+
+    if delete_remote {
+        let refname = local
+            .get()
+            .name()
+            .context("branch name is not valid UTF-8")?
+            .to_string();
+        match (
+            repo.branch_upstream_remote(&refname),
+            repo.branch_upstream_merge(&refname),
+        ) {
+            (Ok(remote), Ok(merge)) => {
+                let remote = remote.as_str().context("remote name is not valid UTF-8")?;
+                let merge = merge.as_str().context("branch name is not valid UTF-8")?;
+                let upstream = merge.strip_prefix("refs/heads/").unwrap_or(merge);
+                // A branch started from another one, say with `--track
+                // origin/main`, tracks that branch rather than its own
+                if upstream != branch {
+                    warning!(
+                        "Branch '{branch}' tracks '{remote}/{upstream}', not a remote branch of \
+                         its own; not deleting it on '{remote}'"
+                    );
+                } else if is_remote_default_branch(&repo, remote, upstream) {
+                    warning!(
+                        "'{remote}/{upstream}' is the default branch of '{remote}'; \
+                         not deleting it"
+                    );
                 } else {
-                    return Err(anyhow::anyhow!(
-                        "Failed to create branch '{}': {}",
-                        branch_name,
-                        e
-                    ));
+                    delete_remote_branch(&repo, remote, merge)?;
+                    info!("Deleted '{merge}' on remote '{remote}'");
                 }
             }
+            _ => {
+                warning!("Branch '{branch}' has no upstream to delete");
+            }
         }
-    } else {
-        println!("Branch '{branch_name}' already exists, using existing branch.",);
     }
 
-    // Create the worktree using git2
-    let opts = git2::WorktreeAddOptions::new();
-    let _worktree = repo
-        .worktree(&worktree_name, &new_folder, Some(&opts))
-        .with_context(|| format!("Failed to create worktree '{worktree_name}'"))?;
+    local
+        .delete()
+        .with_context(|| format!("Failed to delete branch '{branch}'"))?;
+    info!("Deleted branch '{branch}'");
+    Ok(())
+}
 
-    // Checkout the branch in the worktree
-    let worktree_repo = Repository::open(&new_folder).context("failed to open worktree repo")?;
-    let branch_ref = repo
-        .find_branch(branch_name, git2::BranchType::Local)
-        .with_context(|| format!("Failed to find branch '{branch_name}'"))?;
-    let branch_oid = branch_ref
-        .get()
-        .target()
-        .with_context(|| format!("Failed to get branch target for '{branch_name}'"))?;
-
-    worktree_repo
-        .checkout_tree(&worktree_repo.find_object(branch_oid, None)?, None)
-        .with_context(|| format!("Failed to checkout branch '{branch_name}' in worktree"))?;
-
-    // Set the HEAD to point to the branch
-    worktree_repo
-        .set_head(&format!("refs/heads/{branch_name}"))
-        .with_context(|| format!("Failed to set HEAD for worktree '{worktree_name}'"))?;
-
-    println!("Worktree created at {new_folder:?}");
-    Ok(worktree_name)
-}
-
-pub fn remove_worktree(repo_path: &str, worktree_name: &str) -> Result<()> {
-    // Find the worktree path
-    let worktree_path = get_worktree_path(repo_path, worktree_name)
-        .ok_or_else(|| anyhow::anyhow!("Could not find worktree path for {}", worktree_name))?;
-    // Use git CLI to remove the worktree (git2 doesn't have direct worktree removal)
-    let output = Command::new("git")
-        .args(["worktree", "remove", "--force", &worktree_path])
-        .output()
-        .with_context(|| format!("Failed to run git worktree remove for {worktree_path}"))?;
-    if !output.status.success() {
-        return Err(anyhow::anyhow!(
-            "Failed to remove worktree: {}\n{}",
-            worktree_path,
-            String::from_utf8_lossy(&output.stderr)
-        ));
+/// Whether `branch` is what `<remote>/HEAD` points at
+fn is_remote_default_branch(repo: &Repository, remote: &str, branch: &str) -> bool {
+    repo.find_reference(&format!("refs/remotes/{remote}/HEAD"))
+        .ok()
+        .and_then(|head| head.symbolic_target().map(str::to_string))
+        .is_some_and(|target| target == format!("refs/remotes/{remote}/{branch}"))
+}
+
+/// Push a deletion of `refname` to `remote_name`
+fn delete_remote_branch(repo: &Repository, remote_name: &str, refname: &str) -> Result<()> {
+    push_refspec(repo, remote_name, &format!(":{refname}"))
+        .with_context(|| format!("Failed to delete '{refname}' on remote '{remote_name}'"))
+}
+
+/// Push the local branch `branch` to its upstream. Returns the upstream's
+/// name, or `None` if the branch has no upstream to push to.
+pub fn push_branch(repo_path: &str, branch: &str) -> Result<Option<String>> {
+    let repo = discover_repo(repo_path)?;
+    let refname = format!("refs/heads/{branch}");
+    let (Ok(remote), Ok(merge)) = (
+        repo.branch_upstream_remote(&refname),
+        repo.branch_upstream_merge(&refname),
+    ) else {
+        return Ok(None);
+    };
+    let remote = remote.as_str().context("remote name is not valid UTF-8")?;
+    let merge = merge.as_str().context("branch name is not valid UTF-8")?;
+    push_refspec(&repo, remote, &format!("{refname}:{merge}"))
+        .with_context(|| format!("Failed to push '{branch}' to remote '{remote}'"))?;
+    let upstream = merge.strip_prefix("refs/heads/").unwrap_or(merge);
+    Ok(Some(format!("{remote}/{upstream}")))
+}
+
+/// Push `refspec` to `remote_name`, failing if the remote rejects it
+fn push_refspec(repo: &Repository, remote_name: &str, refspec: &str) -> Result<()> {
+    let mut remote = repo
+        .find_remote(remote_name)
+        .with_context(|| format!("Failed to find remote '{remote_name}'"))?;
+    let rejection = std::cell::RefCell::new(None);
+    let mut callbacks = remote_callbacks(repo);
+    callbacks.push_update_reference(|_, status| {
+        if let Some(status) = status {
+            *rejection.borrow_mut() = Some(status.to_string());
+        }
+        Ok(())
+    });
+    let mut options = git2::PushOptions::new();
+    options.remote_callbacks(callbacks);
+    remote.push(&[refspec], Some(&mut options))?;
+    if let Some(status) = rejection.take() {
+        anyhow::bail!("remote '{remote_name}' rejected '{refspec}': {status}");
     }
-    println!("Worktree '{worktree_name}' removed successfully");
     Ok(())
 }
 
-pub fn change_directory(path: &str) -> Result<()> {
-    std::env::set_current_dir(path)
-        .with_context(|| format!("failed to change directory to: {path}"))?;
-    println!("Changed directory to: {path}");
-    Ok(())
+/// Options for `trees merge`
+pub struct MergeOptions {
+    pub strategy: MergeStrategy,
+    /// Message for the merge or squash commit instead of the default
+    pub message: Option<String>,
+    /// Only work out what would happen
+    pub dry_run: bool,
+}
+
+/// What `trees merge` did, or with `--dry-run` would do
+#[derive(Debug, PartialEq)]
+pub enum MergeOutcome {
+    /// The target already contains everything on the source
+    UpToDate,
+    FastForward,
+    /// A merge commit, squash commit or rebase without conflicts
+    Clean,
+    /// The branches have diverged, so `ff-only` can't fast-forward
+    Diverged,
+    /// The merge or rebase conflicts in these paths
+    Conflict(Vec<String>),
+}
+
+/// A merge worked out in memory, ready to be applied
+enum MergePlan {
+    FastForward,
+    /// Commit the merged index on top of the target
+    Commit(git2::Index),
+    /// Move the source to its rebased tip, then fast-forward the target to it
+    Rebase(git2::Oid),
 }
 
-pub fn merge_worktrees(repo_path: &str, source: &str, target: &str) -> Result<()> {
+/// Bring the branch of the worktree `source` into the worktree `target` using
+/// `options.strategy`. Everything is computed in memory first, so a conflict
+/// leaves both worktrees untouched and is returned as
+/// `MergeOutcome::Conflict`; `start_conflicted_merge` writes it out for
+/// resolving. With `options.dry_run` nothing is changed and diverged branches
+/// are reported instead of failing.
+pub fn merge_worktrees(
+    repo_path: &str,
+    source: &str,
+    target: &str,
+    options: &MergeOptions,
+) -> Result<MergeOutcome> {
     // Get worktree paths
     let source_path = get_worktree_path(repo_path, source)
         .ok_or_else(|| anyhow::anyhow!("Could not find worktree path for {}", source))?;
@@ -285,159 +854,1729 @@ pub fn merge_worktrees(repo_path: &str, source: &str, target: &str) -> Result<()
     // Get branch names
     let source_branch = get_worktree_branch(&source_path)?;
     let target_branch = get_worktree_branch(&target_path)?;
+    for (worktree, branch) in [(source, &source_branch), (target, &target_branch)] {
+        anyhow::ensure!(
+            branch != "detached",
+            "Worktree '{worktree}' is not on a branch"
+        );
+    }
+    anyhow::ensure!(
+        options.message.is_none()
+            || matches!(
+                options.strategy,
+                MergeStrategy::Merge | MergeStrategy::NoFf | MergeStrategy::Squash
+            ),
+        "--message only applies to the merge, no-ff and squash strategies"
+    );
+
+    let source_repo = Repository::open(&source_path).context("failed to open worktree repo")?;
+    let target_repo = Repository::open(&target_path).context("failed to open worktree repo")?;
+    let source_ref = format!("refs/heads/{source_branch}");
+    let target_ref = format!("refs/heads/{target_branch}");
+    let source_tip = target_repo
+        .refname_to_id(&source_ref)
+        .with_context(|| format!("Branch '{source_branch}' has no commits"))?;
+    let target_tip = target_repo
+        .refname_to_id(&target_ref)
+        .with_context(|| format!("Branch '{target_branch}' has no commits"))?;
+
+    if source_tip == target_tip || target_repo.graph_descendant_of(target_tip, source_tip)? {
+        info!("'{target}' ({target_branch}) already contains '{source}' ({source_branch})");
+        return Ok(MergeOutcome::UpToDate);
+    }
+    let can_fast_forward = target_repo.graph_descendant_of(source_tip, target_tip)?;
+
+    let plan = match (options.strategy, can_fast_forward) {
+        (MergeStrategy::FfOnly | MergeStrategy::Merge | MergeStrategy::Rebase, true) => {
+            Ok(MergePlan::FastForward)
+        }
+        (MergeStrategy::FfOnly, false) => Err(MergeOutcome::Diverged),
+        (MergeStrategy::Rebase, false) => rebase_in_memory(&target_repo, source_tip, target_tip)?
+            .map(MergePlan::Rebase)
+            .map_err(MergeOutcome::Conflict),
+        (MergeStrategy::Merge | MergeStrategy::NoFf | MergeStrategy::Squash, _) => {
+            merge_in_memory(&target_repo, target_tip, source_tip)?
+                .map(MergePlan::Commit)
+                .map_err(MergeOutcome::Conflict)
+        }
+    };
+    if options.dry_run {
+        return Ok(match plan {
+            Ok(MergePlan::FastForward) => MergeOutcome::FastForward,
+            Ok(_) => MergeOutcome::Clean,
+            Err(outcome) => outcome,
+        });
+    }
+    let plan = match plan {
+        Ok(plan) => plan,
+        Err(MergeOutcome::Conflict(paths)) if options.strategy == MergeStrategy::Rebase => {
+            anyhow::bail!(
+                "Rebasing '{source_branch}' onto '{target_branch}' conflicts in {}; nothing was \
+                 changed (use --strategy merge or squash to resolve conflicts)",
+                paths.join(", ")
+            )
+        }
+        // The caller decides whether to write the conflicts out and resolve them
+        Err(conflict @ MergeOutcome::Conflict(_)) => return Ok(conflict),
+        Err(_) => anyhow::bail!(
+            "Cannot fast-forward '{target_branch}' to '{source_branch}', they have diverged \
+             (try --strategy rebase)"
+        ),
+    };
 
-    println!("Merging '{source}' ({source_dirty}) into '{target}' ({target_branch})",);
+    info!(
+        "Merging '{source}' ({source_branch}) into '{target}' ({target_branch}) with {}",
+        options.strategy.as_str()
+    );
+    let fast_forward = |tip: git2::Oid, message: &str| {
+        move_branch(&target_repo, &target_ref, tip, message, false)
+            .with_context(|| format!("Failed to update '{target}'"))
+    };
+    let outcome = match plan {
+        MergePlan::FastForward => {
+            fast_forward(source_tip, &format!("merge {source_branch}: Fast-forward"))?;
+            MergeOutcome::FastForward
+        }
+        MergePlan::Commit(mut index) => {
+            let squash = options.strategy == MergeStrategy::Squash;
+            let message = match &options.message {
+                Some(message) => message.clone(),
+                None if squash => {
+                    squash_message(&target_repo, &source_branch, source_tip, target_tip)?
+                }
+                None => format!("Merge branch '{source_branch}' into {target_branch}"),
+            };
+            let target_commit = target_repo.find_commit(target_tip)?;
+            let source_commit = target_repo.find_commit(source_tip)?;
+            let parents: Vec<&git2::Commit> = if squash {
+                vec![&target_commit]
+            } else {
+                vec![&target_commit, &source_commit]
+            };
+            let tree = index
+                .write_tree_to(&target_repo)
+                .and_then(|oid| target_repo.find_tree(oid))
+                .context("failed to write merged tree")?;
+            let signature = target_repo
+                .signature()
+                .context("set user.name and user.email to merge")?;
+            let merged = target_repo
+                .commit(None, &signature, &signature, &message, &tree, &parents)
+                .context("failed to create merge commit")?;
+            let reflog = if squash {
+                format!("merge --squash {source_branch}")
+            } else {
+                format!("merge {source_branch}: Merge")
+            };
+            fast_forward(merged, &reflog)?;
+            MergeOutcome::Clean
+        }
+        MergePlan::Rebase(rebased) => {
+            move_branch(
+                &source_repo,
+                &source_ref,
+                rebased,
+                &format!("rebase onto {target_branch}"),
+                false,
+            )
+            .with_context(|| format!("Failed to update '{source}'"))?;
+            fast_forward(rebased, &format!("merge {source_branch}: Fast-forward"))?;
+            MergeOutcome::Clean
+        }
+    };
 
-    // Switch to target worktree and merge source branch using git CLI
-    let output = Command::new("git")
-        .args(["merge", &source_branch])
-        .current_dir(&target_path)
-        .output()
-        .with_context(|| format!("Failed to merge {source_branch} into {target_branch}"))?;
+    info!("Successfully merged '{source}' into '{target}'");
+    Ok(outcome)
+}
 
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(anyhow::anyhow!(
-            "Failed to merge worktrees: {}\n{}",
-            String::from_utf8_lossy(&output.stdout),
-            stderr
-        ));
+/// Merge `source` into `target` in memory. Returns the merged index, or the
+/// conflicting paths.
+fn merge_in_memory(
+    repo: &Repository,
+    target: git2::Oid,
+    source: git2::Oid,
+) -> Result<std::result::Result<git2::Index, Vec<String>>> {
+    let target = repo.find_commit(target).context("failed to find commit")?;
+    let source = repo.find_commit(source).context("failed to find commit")?;
+    let index = repo
+        .merge_commits(&target, &source, None)
+        .context("failed to merge")?;
+    if index.has_conflicts() {
+        return Ok(Err(conflicting_paths(&index)?));
     }
+    Ok(Ok(index))
+}
 
-    println!("Successfully merged '{source}' into '{target}'");
+/// Merge the branch `source_branch` into the worktree at `target_path` the
+/// way `git merge` does when it stops for conflicts: conflict markers in the
+/// files, the conflicts in the index and `MERGE_HEAD` and `MERGE_MSG` set.
+/// A squash writes `SQUASH_MSG` instead of `MERGE_HEAD`, like `git merge
+/// --squash`.
+pub fn start_conflicted_merge(
+    target_path: &str,
+    source_branch: &str,
+    options: &MergeOptions,
+) -> Result<()> {
+    let repo = Repository::open(target_path).context("failed to open worktree repo")?;
+    let source = repo
+        .find_reference(&format!("refs/heads/{source_branch}"))
+        .with_context(|| format!("Failed to find branch '{source_branch}'"))?;
+    let source_commit = repo.reference_to_annotated_commit(&source)?;
+    let mut checkout = git2::build::CheckoutBuilder::new();
+    checkout
+        .safe()
+        .allow_conflicts(true)
+        .conflict_style_merge(true);
+    repo.merge(&[&source_commit], None, Some(&mut checkout))
+        .with_context(|| format!("Failed to merge '{source_branch}'"))?;
+
+    let git_dir = repo.path();
+    if options.strategy == MergeStrategy::Squash {
+        let target_tip = repo.head()?.peel_to_commit()?.id();
+        let message = match &options.message {
+            Some(message) => message.clone(),
+            None => squash_message(&repo, source_branch, source_commit.id(), target_tip)?,
+        };
+        repo.cleanup_state()
+            .context("failed to clean up merge state")?;
+        fs::write(git_dir.join("SQUASH_MSG"), message).context("failed to write SQUASH_MSG")?;
+    } else if let Some(message) = &options.message {
+        fs::write(git_dir.join("MERGE_MSG"), message).context("failed to write MERGE_MSG")?;
+    }
     Ok(())
 }
 
-pub fn pull_all_worktrees(repo_path: &str) -> Result<()> {
-    // Open the repository
-    let repo = Repository::open(repo_path).context("failed to open git repo")?;
+/// Whether the worktree is in the middle of a merge: `MERGE_HEAD` is set or
+/// its index has conflicts
+pub fn merge_in_progress(worktree_path: &str) -> Result<bool> {
+    let repo = Repository::open(worktree_path).context("failed to open worktree repo")?;
+    Ok(repo.path().join("MERGE_HEAD").exists() || repo.index()?.has_conflicts())
+}
 
-    // Fetch all remote branches using git2
-    let remote_callbacks = git2::RemoteCallbacks::new();
-    let mut fetch_options = git2::FetchOptions::new();
-    fetch_options.remote_callbacks(remote_callbacks);
+/// Files with unresolved conflicts in the worktree
+pub fn conflicted_files(worktree_path: &str) -> Result<Vec<String>> {
+    let repo = Repository::open(worktree_path).context("failed to open worktree repo")?;
+    conflicting_paths(&repo.index()?)
+}
 
-    // Get all remotes
-    let remotes = repo.remotes().with_context(|| "Failed to get remotes")?;
+/// Resolve the conflict in `file` by taking the target's version (`ours`) or
+/// the source's (`theirs`). A side that deleted the file deletes it.
+pub fn take_side(worktree_path: &str, file: &str, theirs: bool) -> Result<()> {
+    let repo = Repository::open(worktree_path).context("failed to open worktree repo")?;
+    let mut index = repo.index()?;
+    let mut side = None;
+    for conflict in index.conflicts()? {
+        let conflict = conflict?;
+        let path = conflict
+            .our
+            .as_ref()
+            .or(conflict.their.as_ref())
+            .or(conflict.ancestor.as_ref())
+            .map(|entry| entry.path.clone());
+        if path.as_deref() == Some(file.as_bytes()) {
+            side = Some(if theirs { conflict.their } else { conflict.our });
+            break;
+        }
+    }
+    let side = side.with_context(|| format!("'{file}' has no conflict"))?;
+
+    let full_path = Path::new(worktree_path).join(file);
+    match side {
+        Some(entry) => {
+            let blob = repo.find_blob(entry.id)?;
+            fs::write(&full_path, blob.content())
+                .with_context(|| format!("Failed to write {}", full_path.display()))?;
+            index.add_path(Path::new(file))?;
+        }
+        None => {
+            if full_path.exists() {
+                fs::remove_file(&full_path)
+                    .with_context(|| format!("Failed to delete {}", full_path.display()))?;
+            }
+            index.remove_path(Path::new(file))?;
+        }
+    }
+    index.write().context("failed to write index")?;
+    Ok(())
+}
 
-    for name in remotes.iter().flatten() {
-        let mut remote = repo
-            .find_remote(name)
-            .with_context(|| format!("Failed to find remote '{name}'"))?;
-        remote
-            .fetch(&[] as &[&str], Some(&mut fetch_options), None)
-            .with_context(|| format!("Failed to fetch from remote '{name}'"))?;
-        println!("Fetched from remote '{name}'");
+/// Mark `file` as resolved by staging it as it is in the worktree
+pub fn mark_resolved(worktree_path: &str, file: &str) -> Result<()> {
+    let repo = Repository::open(worktree_path).context("failed to open worktree repo")?;
+    let mut index = repo.index()?;
+    if Path::new(worktree_path).join(file).exists() {
+        index.add_path(Path::new(file))?;
+    } else {
+        index.remove_path(Path::new(file))?;
+    }
+    index.write().context("failed to write index")?;
+    Ok(())
+}
+
+/// Commit a merge whose conflicts are all resolved, with the message git
+/// prepared for it
+pub fn continue_merge(worktree_path: &str) -> Result<()> {
+    let repo = Repository::open(worktree_path).context("failed to open worktree repo")?;
+    let mut index = repo.index()?;
+    anyhow::ensure!(!index.has_conflicts(), "Some files still have conflicts");
+
+    let git_dir = repo.path().to_path_buf();
+    let head = repo.head()?.peel_to_commit()?;
+    let mut parents = vec![head];
+    if let Ok(merge_heads) = fs::read_to_string(git_dir.join("MERGE_HEAD")) {
+        for line in merge_heads.lines() {
+            parents.push(repo.find_commit(git2::Oid::from_str(line.trim())?)?);
+        }
     }
+    let message = repo
+        .message()
+        .or_else(|_| fs::read_to_string(git_dir.join("SQUASH_MSG")))
+        .context("failed to read the merge message")?;
+    let message = git2::message_prettify(message, Some(b'#'))?;
+
+    let tree = repo.find_tree(index.write_tree().context("failed to write tree")?)?;
+    let signature = repo
+        .signature()
+        .context("set user.name and user.email to merge")?;
+    let parents: Vec<&git2::Commit> = parents.iter().collect();
+    repo.commit(
+        Some("HEAD"),
+        &signature,
+        &signature,
+        &message,
+        &tree,
+        &parents,
+    )
+    .context("failed to commit merge")?;
+    clear_merge_state(&repo)
+}
 
-    println!("Fetched all remote branches");
+/// Abandon a merge in progress and put the worktree back at HEAD
+pub fn abort_merge(worktree_path: &str) -> Result<()> {
+    let repo = Repository::open(worktree_path).context("failed to open worktree repo")?;
+    let head = repo.head()?.peel_to_commit()?;
+    repo.reset(head.as_object(), git2::ResetType::Hard, None)
+        .context("failed to reset worktree")?;
+    clear_merge_state(&repo)
+}
 
-    // Get all worktrees
-    let worktrees = get_worktrees(repo_path)?;
+fn clear_merge_state(repo: &Repository) -> Result<()> {
+    repo.cleanup_state()
+        .context("failed to clean up merge state")?;
+    let squash_message = repo.path().join("SQUASH_MSG");
+    if squash_message.exists() {
+        fs::remove_file(squash_message).context("failed to remove SQUASH_MSG")?;
+    }
+    Ok(())
+}
 
-    for worktree in &worktrees {
-        let worktree_path = &worktree.path;
+/// What `trees conflicts` predicts for merging worktrees' branches
+pub struct ConflictReport {
+    /// What was compared: worktree names, and with `against_default` the
+    /// default branch last
+    pub names: Vec<String>,
+    /// The default branch, when each worktree was checked against it only
+    pub default_branch: Option<String>,
+    /// The result of merging `names[j]` into `names[i]` for each pair `(i, j)`
+    /// that was checked
+    pub results: BTreeMap<(usize, usize), MergeOutcome>,
+}
 
-        // Check if worktree is dirty
-        if worktree.is_dirty {
-            println!(
-                "Skipping worktree '{}' - has uncommitted changes",
-                worktree.name
-            );
+/// Predict, in memory, the result of merging every pair of `worktrees`, or
+/// with `against_default` each worktree with the default branch. Worktrees
+/// whose directory is gone or whose branch has no commits are left out.
+pub fn predict_conflicts(
+    repo_path: &str,
+    worktrees: &[WorktreeInfo],
+    against_default: bool,
+) -> Result<ConflictReport> {
+    let repo = discover_repo(repo_path)?;
+    let default = default_branch(&repo);
+    let mut names = Vec::new();
+    let mut tips = Vec::new();
+    for worktree in worktrees.iter().filter(|wt| wt.state.is_present()) {
+        if against_default
+            && default
+                .as_ref()
+                .is_some_and(|(name, _)| *name == worktree.branch)
+        {
             continue;
         }
+        let tip = Repository::open(&worktree.path)
+            .ok()
+            .and_then(|wt_repo| wt_repo.head().ok()?.target());
+        if let Some(tip) = tip {
+            names.push(worktree.name.clone());
+            tips.push(tip);
+        }
+    }
 
-        // Get the branch name
-        let branch_name = &worktree.branch;
-
-        // Check if there are updates available
-        let status_output = Command::new("git")
-            .args(["status", "--porcelain", "--branch"])
-            .current_dir(worktree_path)
-            .output()
-            .with_context(|| format!("Failed to check status for worktree {}", worktree.name))?;
-
-        let status_str = String::from_utf8_lossy(&status_output.stdout);
-
-        // Check if behind remote
-        if status_str.contains("[behind") {
-            println!(
-                "Pulling updates for worktree '{}' (branch: {})",
-                worktree.name, branch_name
-            );
+    let mut pairs = Vec::new();
+    let default_branch = if against_default {
+        let (name, tip) =
+            default.context("No default branch found (origin/HEAD, main or master)")?;
+        pairs.extend((0..names.len()).map(|i| (i, names.len())));
+        names.push(name.clone());
+        tips.push(tip);
+        Some(name)
+    } else {
+        for i in 0..names.len() {
+            pairs.extend((i + 1..names.len()).map(|j| (i, j)));
+        }
+        None
+    };
 
-            let pull_output = Command::new("git")
-                .args(["pull"])
-                .current_dir(worktree_path)
-                .output()
-                .with_context(|| format!("Failed to pull for worktree {}", worktree.name))?;
-
-            if pull_output.status.success() {
-                println!(
-                    "Successfully pulled updates for worktree '{}'",
-                    worktree.name
-                );
-            } else {
-                println!(
-                    "Failed to pull updates for worktree '{}': {}",
-                    worktree.name,
-                    String::from_utf8_lossy(&pull_output.stderr)
-                );
+    let mut results = BTreeMap::new();
+    let mut error = None;
+    run_parallel(
+        pairs,
+        default_jobs(),
+        |(i, j)| {
+            let outcome =
+                discover_repo(repo_path).and_then(|repo| predict_merge(&repo, tips[i], tips[j]));
+            ((i, j), outcome)
+        },
+        |(pair, outcome)| match outcome {
+            Ok(outcome) => {
+                results.insert(pair, outcome);
             }
-        } else {
-            println!("Worktree '{}' is up to date", worktree.name);
-        }
+            Err(e) => {
+                error.get_or_insert(e);
+            }
+        },
+    );
+    if let Some(e) = error {
+        return Err(e);
     }
+    Ok(ConflictReport {
+        names,
+        default_branch,
+        results,
+    })
+}
 
-    Ok(())
+/// What merging `theirs` into `ours` would do, worked out in memory
+fn predict_merge(repo: &Repository, ours: git2::Oid, theirs: git2::Oid) -> Result<MergeOutcome> {
+    if ours == theirs || repo.graph_descendant_of(ours, theirs)? {
+        return Ok(MergeOutcome::UpToDate);
+    }
+    if repo.graph_descendant_of(theirs, ours)? {
+        return Ok(MergeOutcome::FastForward);
+    }
+    Ok(match merge_in_memory(repo, ours, theirs)? {
+        Ok(_) => MergeOutcome::Clean,
+        Err(paths) => MergeOutcome::Conflict(paths),
+    })
 }
 
-fn get_worktree_branch(worktree_path: &str) -> Result<String> {
-    // For worktrees, the .git is a file that points to the worktree directory in the main repo
-    let git_file_path = Path::new(worktree_path).join(".git");
-    if git_file_path.is_file() {
-        // This is a worktree, read the gitdir from the .git file
-        let git_content = fs::read_to_string(&git_file_path)
-            .with_context(|| format!("Failed to read .git file at {git_file_path:?}"))?;
-        if let Some(gitdir) = git_content.strip_prefix("gitdir: ") {
-            let gitdir_path = gitdir.trim();
-            let head_path = Path::new(gitdir_path).join("HEAD");
-            let head_content = fs::read_to_string(&head_path)
-                .with_context(|| format!("Failed to read HEAD file at {head_path:?}"))?;
-            if let Some(stripped) = head_content.strip_prefix("ref: refs/heads/") {
-                Ok(stripped.trim().to_string())
-            } else {
-                Ok("detached".to_string())
-            }
-        } else {
-            Ok("unknown".to_string())
-        }
-    } else {
-        // This is a regular repository, read .git/HEAD directly
-        let head_path = Path::new(worktree_path).join(".git/HEAD");
-        let head_content = fs::read_to_string(&head_path)
-            .with_context(|| format!("Failed to read HEAD file at {head_path:?}"))?;
-        if let Some(stripped) = head_content.strip_prefix("ref: refs/heads/") {
-            Ok(stripped.trim().to_string())
-        } else {
-            Ok("detached".to_string())
+/// Paths with conflicts in `index`
+fn conflicting_paths(index: &git2::Index) -> Result<Vec<String>> {
+    let mut paths = Vec::new();
+    for conflict in index.conflicts()? {
+        let conflict = conflict?;
+        if let Some(entry) = conflict.our.or(conflict.their).or(conflict.ancestor) {
+            paths.push(String::from_utf8_lossy(&entry.path).to_string());
         }
     }
+    Ok(paths)
 }
 
-fn is_worktree_dirty(worktree_path: &str) -> Result<bool> {
-    let repo = Repository::open(worktree_path).context("failed to open worktree repo")?;
+/// Default squash commit message: the branch plus the subjects of the
+/// commits being squashed, oldest first
+fn squash_message(
+    repo: &Repository,
+    branch: &str,
+    source: git2::Oid,
+    target: git2::Oid,
+) -> Result<String> {
+    let mut revwalk = repo.revwalk()?;
+    revwalk.set_sorting(git2::Sort::TOPOLOGICAL | git2::Sort::REVERSE)?;
+    revwalk.push(source)?;
+    revwalk.hide(target)?;
+    let mut message = format!("Squashed branch '{branch}'\n");
+    for oid in revwalk {
+        let commit = repo.find_commit(oid?)?;
+        message.push_str(&format!("\n* {}", commit.summary().unwrap_or_default()));
+    }
+    Ok(message)
+}
 
-    // Check if there are any changes in the working directory
-    let mut options = git2::StatusOptions::new();
-    options.include_untracked(true);
-    options.include_ignored(false);
+/// Fetch all branches of the remote `name` using its configured refspecs
+/// With `prune`, remote-tracking branches deleted on the remote are removed;
+/// otherwise the remote's `prune` setting decides.
+fn fetch_remote<'repo>(
+    repo: &'repo Repository,
+    name: &str,
+    callbacks: git2::RemoteCallbacks<'_>,
+    prune: bool,
+) -> Result<git2::Remote<'repo>> {
+    let mut fetch_options = git2::FetchOptions::new();
+    fetch_options.remote_callbacks(callbacks);
+    if prune {
+        fetch_options.prune(git2::FetchPrune::On);
+    }
+
+    let mut remote = repo
+        .find_remote(name)
+        .with_context(|| format!("Failed to find remote '{name}'"))?;
+    {
+        // Connecting touches nothing on disk, so fetches of several remotes
+        // do it at the same time. Downloading writes the repository's shallow
+        // file and updating the tips its FETCH_HEAD, under lock files that
+        // fail instead of waiting, so that part takes turns.
+        let mut connection = remote
+            .connect_auth(git2::Direction::Fetch, Some(remote_callbacks(repo)), None)
+            .with_context(|| format!("Failed to fetch from remote '{name}'"))?;
+        let _lock = FETCH_LOCK.lock().unwrap();
+        connection
+            .remote()
+            .fetch(&[] as &[&str], Some(&mut fetch_options), None)
+            .with_context(|| format!("Failed to fetch from remote '{name}'"))?;
+    }
+    Ok(remote)
+}
+
+/// Fetches into one repository take turns writing to it, see `fetch_remote`
+static FETCH_LOCK: Mutex<()> = Mutex::new(());
+
+/// Clone `url` into `<parent>/<name>/.bare` with a `.git` pointer file next to
+/// it and a first worktree for the remote's default branch. Returns the path
+/// of that worktree, or of the project directory if the remote is empty.
+pub fn clone_bare(url: &str, parent: &str, name: Option<&str>) -> Result<String> {
+    let name = match name {
+        Some(name) => name.to_string(),
+        None => repo_name_from_url(url)?,
+    };
+    let project_dir = fs::canonicalize(parent)
+        .with_context(|| format!("Failed to canonicalize path: {parent}"))?
+        .join(&name);
+    if project_dir.exists() {
+        return Err(anyhow::anyhow!(
+            "Directory '{}' already exists. Please remove it first or choose a different name.",
+            project_dir.display()
+        ));
+    }
+
+    clone_into(url, &project_dir).inspect_err(|_| {
+        // Leave nothing behind, so that the clone can simply be run again
+        if let Err(e) = fs::remove_dir_all(&project_dir) {
+            warning!("Failed to remove {project_dir:?}: {e}");
+        }
+    })
+}
+
+/// The steps of `clone_bare` once `project_dir` is known not to exist
+fn clone_into(url: &str, project_dir: &Path) -> Result<String> {
+    let bare_dir = project_dir.join(".bare");
+    info!("Cloning '{url}' into {bare_dir:?}");
+    let repo = Repository::init_bare(&bare_dir)
+        .with_context(|| format!("Failed to create bare repository at {bare_dir:?}"))?;
+    fs::write(project_dir.join(".git"), "gitdir: ./.bare\n")
+        .context("Failed to write .git pointer file")?;
+
+    // A bare repo has no fetch refspec by default, so remote branches would
+    // never show up under refs/remotes/origin
+    repo.remote_with_fetch("origin", url, "+refs/heads/*:refs/remotes/origin/*")
+        .with_context(|| format!("Failed to add remote 'origin' for '{url}'"))?;
+    let remote = fetch_remote(&repo, "origin", remote_callbacks(&repo), false)?;
+    info!("Fetched from remote 'origin'");
+
+    let default_ref = match remote.default_branch() {
+        Ok(default_ref) => default_ref.as_str().map(str::to_string),
+        Err(_) => None,
+    };
+    let Some(default_branch) = default_ref
+        .as_deref()
+        .and_then(|r| r.strip_prefix("refs/heads/"))
+        .filter(|branch| {
+            repo.find_branch(&format!("origin/{branch}"), git2::BranchType::Remote)
+                .is_ok()
+        })
+    else {
+        warning!("Remote has no default branch, not creating a worktree");
+        return Ok(project_dir.to_string_lossy().to_string());
+    };
+
+    repo.set_head(&format!("refs/heads/{default_branch}"))
+        .context("Failed to set HEAD")?;
+    repo.reference_symbolic(
+        "refs/remotes/origin/HEAD",
+        &format!("refs/remotes/origin/{default_branch}"),
+        true,
+        "trees clone",
+    )
+    .context("Failed to set refs/remotes/origin/HEAD")?;
+
+    let options = CreateOptions {
+        track: Some(format!("origin/{default_branch}")),
+        ..Default::default()
+    };
+    create_worktree(&project_dir.to_string_lossy(), default_branch, &options)
+}
+
+/// Directory name git would use when cloning `url`
+fn repo_name_from_url(url: &str) -> Result<String> {
+    let trimmed = url.trim_end_matches('/');
+    let last = trimmed.rsplit(['/', ':']).next().unwrap_or(trimmed);
+    let name = last.strip_suffix(".git").unwrap_or(last);
+    if name.is_empty() {
+        return Err(anyhow::anyhow!(
+            "Could not work out a directory name from '{}', please pass one",
+            url
+        ));
+    }
+    Ok(name.to_string())
+}
+
+/// Convert a normal clone into `<repo>/.bare` plus a worktree for the
+/// checked-out branch, keeping uncommitted changes, the index, stashes, local
+/// branches and their upstreams. With `dry_run` only the plan is printed.
+/// Returns the path of the new worktree.
+pub fn convert_to_bare(repo_path: &str, dry_run: bool) -> Result<String> {
+    let main_repo_path = find_main_repo_path(repo_path)?;
+    let repo = Repository::open(&main_repo_path).context("failed to open git repo")?;
+    let project_dir = PathBuf::from(&main_repo_path);
+    let git_dir = project_dir.join(".git");
+
+    if repo.is_bare() {
+        return Err(anyhow::anyhow!(
+            "'{}' is already a bare repository",
+            main_repo_path
+        ));
+    }
+    if !git_dir.is_dir() || fs::canonicalize(repo.path())? != fs::canonicalize(&git_dir)? {
+        return Err(anyhow::anyhow!(
+            "Only clones with a .git directory inside '{}' can be converted",
+            main_repo_path
+        ));
+    }
+    if repo.state() != git2::RepositoryState::Clean {
+        return Err(anyhow::anyhow!(
+            "A merge, rebase or other operation is in progress. Please finish or abort it first."
+        ));
+    }
+    if project_dir.join(".gitmodules").exists() {
+        return Err(anyhow::anyhow!(
+            "Repositories with submodules cannot be converted"
+        ));
+    }
+
+    let branch_name = get_worktree_branch(&main_repo_path)?;
+    if branch_name == "detached" {
+        return Err(anyhow::anyhow!(
+            "HEAD is detached. Please check out a branch first."
+        ));
+    }
+    if repo
+        .find_branch(&branch_name, git2::BranchType::Local)
+        .is_err()
+    {
+        return Err(anyhow::anyhow!(
+            "Branch '{}' has no commits yet. Please commit first.",
+            branch_name
+        ));
+    }
+
+    let bare_dir = project_dir.join(".bare");
+    let staging_dir = project_dir.join(".trees-convert");
+    for dir in [&bare_dir, &staging_dir] {
+        if dir.exists() {
+            return Err(anyhow::anyhow!(
+                "'{}' already exists. Please remove it first.",
+                dir.display()
+            ));
+        }
+    }
+
+    let mut entries = Vec::new();
+    for entry in fs::read_dir(&project_dir)
+        .with_context(|| format!("Failed to read directory: {main_repo_path}"))?
+    {
+        let entry = entry?;
+        if entry.file_name() != ".git" {
+            entries.push(entry.file_name());
+        }
+    }
+    let linked_worktrees: Vec<(String, PathBuf)> = repo
+        .worktrees()
+        .context("failed to get worktrees")?
+        .iter()
+        .flatten()
+        .filter_map(|name| {
+            let worktree = repo.find_worktree(name).ok()?;
+            Some((name.to_string(), worktree.path().to_path_buf()))
+        })
+        .collect();
+    let stash_count = repo.reflog("refs/stash").map(|log| log.len()).unwrap_or(0);
+    let worktree_name = branch_name.replace('/', "-");
+    let worktree_dir = project_dir.join(&worktree_name);
+    if let Some((_, path)) = linked_worktrees
+        .iter()
+        .find(|(name, _)| *name == worktree_name)
+    {
+        return Err(anyhow::anyhow!(
+            "The linked worktree at {path:?} is named '{worktree_name}', the name the worktree \
+             for branch '{branch_name}' needs. Remove it first."
+        ));
+    }
+
+    let mut plan = vec![
+        format!("Converting {project_dir:?} to a bare repository with worktrees:"),
+        format!("  move {git_dir:?} to {bare_dir:?} and set core.bare = true"),
+        format!("  write {git_dir:?} pointing at ./.bare"),
+        format!("  create worktree {worktree_dir:?} for branch '{branch_name}'"),
+        format!(
+            "  move {} working tree entries and the index into it, keeping uncommitted changes",
+            entries.len()
+        ),
+    ];
+    for (name, path) in &linked_worktrees {
+        plan.push(format!("  repoint linked worktree '{name}' at {path:?}"));
+    }
+    plan.push(format!(
+        "  keep {stash_count} stash(es), local branches and upstreams"
+    ));
+    if dry_run {
+        // The plan is what was asked for, so `--quiet` doesn't hide it
+        for line in &plan {
+            println!("{line}");
+        }
+        return Ok(project_dir.to_string_lossy().to_string());
+    }
+    for line in &plan {
+        info!("{line}");
+    }
+    drop(repo);
+
+    let conversion = Conversion {
+        project_dir,
+        git_dir,
+        bare_dir,
+        staging_dir,
+        worktree_dir,
+        entries,
+        linked_worktrees,
+    };
+    let mut created_worktree = false;
+    match conversion.run(&branch_name, &mut created_worktree) {
+        Ok(worktree_path) => {
+            info!(
+                "Converted {:?}, branch '{branch_name}' is now at {worktree_path:?}",
+                conversion.project_dir
+            );
+            Ok(worktree_path)
+        }
+        Err(e) => match conversion.undo(created_worktree) {
+            Ok(()) => Err(e.context("Conversion failed; the clone was put back as it was")),
+            Err(undo) => Err(e.context(format!(
+                "Conversion failed and could not be undone ({undo:#}). To restore the clone by \
+                 hand, in {project:?}: move everything except .git out of {worktree:?} and \
+                 {staging:?} back into {project:?}, delete {worktree:?}, {staging:?} and the \
+                 .git file, rename .bare to .git, delete .git/worktrees/{name}, and run `git \
+                 config core.bare false`",
+                project = conversion.project_dir,
+                worktree = conversion.worktree_dir,
+                staging = conversion.staging_dir,
+                name = worktree_name,
+            ))),
+        },
+    }
+}
+
+/// The paths involved in `convert_to_bare`, so a failed conversion can be
+/// undone
+struct Conversion {
+    project_dir: PathBuf,
+    git_dir: PathBuf,
+    bare_dir: PathBuf,
+    staging_dir: PathBuf,
+    worktree_dir: PathBuf,
+    /// Working tree entries of the project directory, other than .git
+    entries: Vec<std::ffi::OsString>,
+    linked_worktrees: Vec<(String, PathBuf)>,
+}
+
+impl Conversion {
+    /// Convert, setting `created_worktree` once the new worktree directory
+    /// may exist. Returns the path of the new worktree.
+    fn run(&self, branch_name: &str, created_worktree: &mut bool) -> Result<String> {
+        let Conversion {
+            project_dir,
+            git_dir,
+            bare_dir,
+            staging_dir,
+            entries,
+            ..
+        } = self;
+
+        // Park the working tree so the new worktree directory starts out empty
+        fs::create_dir(staging_dir)
+            .with_context(|| format!("Failed to create directory {staging_dir:?}"))?;
+        for entry in entries {
+            fs::rename(project_dir.join(entry), staging_dir.join(entry))
+                .with_context(|| format!("Failed to move {entry:?} to {staging_dir:?}"))?;
+        }
+
+        fs::rename(git_dir, bare_dir)
+            .with_context(|| format!("Failed to move {git_dir:?} to {bare_dir:?}"))?;
+        set_core_bare(bare_dir, true)?;
+        fs::write(git_dir, "gitdir: ./.bare\n").context("Failed to write .git pointer file")?;
+        self.repoint_linked_worktrees(bare_dir)?;
+
+        *created_worktree = true;
+        let worktree_path = create_worktree(
+            &project_dir.to_string_lossy(),
+            branch_name,
+            &CreateOptions::default(),
+        )?;
+        let worktree_dir = PathBuf::from(&worktree_path);
+        let worktree_name = worktree_dir
+            .file_name()
+            .context("Invalid worktree path")?
+            .to_os_string();
+
+        // Swap the fresh checkout for the original files and index
+        for entry in fs::read_dir(&worktree_dir)? {
+            let entry = entry?;
+            if entry.file_name() == ".git" {
+                continue;
+            }
+            if entry.file_type()?.is_dir() {
+                fs::remove_dir_all(entry.path())?;
+            } else {
+                fs::remove_file(entry.path())?;
+            }
+        }
+        for entry in entries {
+            fs::rename(staging_dir.join(entry), worktree_dir.join(entry))
+                .with_context(|| format!("Failed to move {entry:?} into {worktree_dir:?}"))?;
+        }
+        fs::remove_dir(staging_dir).with_context(|| format!("Failed to remove {staging_dir:?}"))?;
+        let index = bare_dir.join("index");
+        if index.exists() {
+            fs::rename(
+                &index,
+                bare_dir.join("worktrees").join(worktree_name).join("index"),
+            )
+            .context("Failed to move the index into the new worktree")?;
+        }
+        Ok(worktree_path)
+    }
+
+    /// Put the project directory back the way it was before `run`
+    fn undo(&self, created_worktree: bool) -> Result<()> {
+        let Conversion {
+            project_dir,
+            git_dir,
+            bare_dir,
+            staging_dir,
+            worktree_dir,
+            entries,
+            ..
+        } = self;
+        let exists = |path: &Path| fs::symlink_metadata(path).is_ok();
+
+        // Entries already moved into the new worktree go back to staging
+        // first: one of them may have the worktree directory's name
+        if created_worktree && exists(worktree_dir) {
+            if !exists(staging_dir) {
+                fs::create_dir(staging_dir)?;
+            }
+            for entry in entries {
+                if exists(&worktree_dir.join(entry)) && !exists(&staging_dir.join(entry)) {
+                    fs::rename(worktree_dir.join(entry), staging_dir.join(entry))?;
+                }
+            }
+            fs::remove_dir_all(worktree_dir)?;
+        }
+        if created_worktree && let Some(name) = worktree_dir.file_name() {
+            let admin_dir = bare_dir.join("worktrees").join(name);
+            if exists(&admin_dir) {
+                fs::remove_dir_all(admin_dir)?;
+            }
+        }
+
+        if exists(bare_dir) {
+            if git_dir.is_file() {
+                fs::remove_file(git_dir)?;
+            }
+            fs::rename(bare_dir, git_dir)?;
+            set_core_bare(git_dir, false)?;
+            self.repoint_linked_worktrees(git_dir)?;
+        }
+
+        if exists(staging_dir) {
+            for entry in entries {
+                if exists(&staging_dir.join(entry)) {
+                    fs::rename(staging_dir.join(entry), project_dir.join(entry))?;
+                }
+            }
+            fs::remove_dir(staging_dir)?;
+        }
+        Ok(())
+    }
+
+    /// Point the linked worktrees at their admin directories in `git_dir`
+    fn repoint_linked_worktrees(&self, git_dir: &Path) -> Result<()> {
+        for (name, path) in &self.linked_worktrees {
+            let admin_dir = git_dir.join("worktrees").join(name);
+            let pointer = path.join(".git");
+            if pointer.is_file() {
+                fs::write(&pointer, format!("gitdir: {}\n", admin_dir.display()))
+                    .with_context(|| format!("Failed to repoint worktree '{name}'"))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn set_core_bare(git_dir: &Path, bare: bool) -> Result<()> {
+    git2::Config::open(&git_dir.join("config"))
+        .and_then(|mut config| config.set_bool("core.bare", bare))
+        .context("Failed to set core.bare")
+}
+
+/// How a remote-tracking branch changed in a fetch
+#[derive(Debug, PartialEq)]
+pub enum BranchChange {
+    New,
+    Deleted,
+    /// Moved to a commit that doesn't contain the old one
+    ForceUpdated {
+        old: git2::Oid,
+        new: git2::Oid,
+    },
+}
+
+/// Remote-tracking branches of one remote that changed in a fetch
+pub struct FetchReport {
+    pub remote: String,
+    /// Changed branches by short name, e.g. `origin/main`, in name order
+    pub changes: Vec<(String, BranchChange)>,
+}
+
+/// Fetch every remote on up to `jobs` threads, with live progress on a
+/// terminal, and report which remote-tracking branches changed. Fails after
+/// all fetches have finished if any of them failed.
+pub fn fetch_all_remotes(repo_path: &str, jobs: usize, prune: bool) -> Result<Vec<FetchReport>> {
+    let repo = discover_repo(repo_path)?;
+    let remotes = repo.remotes().with_context(|| "Failed to get remotes")?;
+    let remotes: Vec<String> = remotes.iter().flatten().map(str::to_string).collect();
+
+    // Each thread opens the repository itself, git2 handles can't be shared
+    let progress = Progress::new(remotes.clone());
+    let mut reports = Vec::new();
+    let mut failures = Vec::new();
+    run_parallel(
+        remotes.iter().enumerate().collect(),
+        jobs,
+        |(index, name)| {
+            progress.set(index, "fetching");
+            let result = discover_repo(repo_path).and_then(|repo| {
+                let before = remote_branch_tips(&repo, name)?;
+                let mut callbacks = remote_callbacks(&repo);
+                callbacks.transfer_progress(|stats| {
+                    progress.set(index, describe_transfer(&stats));
+                    true
+                });
+                fetch_remote(&repo, name, callbacks, prune)?;
+                let after = remote_branch_tips(&repo, name)?;
+                Ok(branch_changes(&repo, &before, &after))
+            });
+            progress.finish(index, if result.is_ok() { "fetched" } else { "failed" });
+            (name, result)
+        },
+        |(name, result)| match result {
+            Ok(changes) => {
+                if !progress.is_live() {
+                    verbose!("Fetched from remote '{name}'");
+                }
+                reports.push(FetchReport {
+                    remote: name.clone(),
+                    changes,
+                });
+            }
+            Err(e) => failures.push(format!("{e:#}")),
+        },
+    );
+    if !failures.is_empty() {
+        return Err(anyhow::anyhow!(failures.join("\n")));
+    }
+    reports.sort_by(|a, b| a.remote.cmp(&b.remote));
+    Ok(reports)
+}
+
+/// Tips of the remote-tracking branches of `remote`, by short name
+fn remote_branch_tips(repo: &Repository, remote: &str) -> Result<BTreeMap<String, git2::Oid>> {
+    let mut tips = BTreeMap::new();
+    let references = repo
+        .references_glob(&format!("refs/remotes/{remote}/*"))
+        .context("failed to list remote branches")?;
+    for reference in references.flatten() {
+        // Skip the symbolic `<remote>/HEAD`
+        if let (Some(name), Some(oid)) = (reference.shorthand(), reference.target()) {
+            tips.insert(name.to_string(), oid);
+        }
+    }
+    Ok(tips)
+}
+
+fn branch_changes(
+    repo: &Repository,
+    before: &BTreeMap<String, git2::Oid>,
+    after: &BTreeMap<String, git2::Oid>,
+) -> Vec<(String, BranchChange)> {
+    let mut changes = Vec::new();
+    for (name, &new) in after {
+        match before.get(name) {
+            None => changes.push((name.clone(), BranchChange::New)),
+            Some(&old) if old != new => {
+                let fast_forward = repo.graph_descendant_of(new, old).unwrap_or(false);
+                if !fast_forward {
+                    changes.push((name.clone(), BranchChange::ForceUpdated { old, new }));
+                }
+            }
+            Some(_) => {}
+        }
+    }
+    for name in before.keys().filter(|name| !after.contains_key(*name)) {
+        changes.push((name.clone(), BranchChange::Deleted));
+    }
+    changes.sort_by(|a, b| a.0.cmp(&b.0));
+    changes
+}
+
+/// Worktrees whose branch is configured to track a remote branch that no
+/// longer exists, with the name of that upstream
+pub fn worktrees_with_gone_upstream(repo_path: &str) -> Result<Vec<(WorktreeInfo, String)>> {
+    let mut gone = Vec::new();
+    for worktree in list_worktrees(repo_path)? {
+        if !worktree.state.is_present() {
+            continue;
+        }
+        let Ok(repo) = Repository::open(&worktree.path) else {
+            continue;
+        };
+        if let Some(upstream) = gone_upstream(&repo) {
+            gone.push((worktree, upstream));
+        }
+    }
+    Ok(gone)
+}
+
+/// Why `trees clean` offers a worktree for removal
+#[derive(Debug, Clone, PartialEq)]
+pub enum StaleReason {
+    /// Everything on the branch is already on the default branch, named here
+    Merged(String),
+    /// The branch's upstream, named here, no longer exists
+    UpstreamGone(String),
+    /// No commits, checkouts or staging for this many days
+    Idle(u64),
+}
+
+impl StaleReason {
+    pub fn describe(&self) -> String {
+        match self {
+            StaleReason::Merged(default) => format!("merged into {default}"),
+            StaleReason::UpstreamGone(upstream) => format!("{upstream} is gone"),
+            StaleReason::Idle(days) => format!("idle for {days} days"),
+        }
+    }
+}
+
+/// A worktree `trees clean` offers for removal and why
+pub struct StaleWorktree {
+    pub worktree: WorktreeInfo,
+    pub reasons: Vec<StaleReason>,
+}
+
+impl StaleWorktree {
+    pub fn describe(&self) -> String {
+        let reasons: Vec<String> = self.reasons.iter().map(StaleReason::describe).collect();
+        reasons.join(", ")
+    }
+}
+
+/// Linked worktrees that look forgotten: merged into the default branch, with
+/// an upstream that was deleted, or idle for at least `idle_days`. The main
+/// worktree, locked worktrees and the default branch's worktree are never
+/// offered.
+pub fn find_stale_worktrees(
+    repo_path: &str,
+    worktrees: Vec<WorktreeInfo>,
+    idle_days: u64,
+) -> Result<Vec<StaleWorktree>> {
+    let repo = discover_repo(repo_path)?;
+    let default = default_branch(&repo);
+    let (local_branches, _) = get_branches(repo_path)?;
+    let now = SystemTime::now();
+
+    let mut stale = Vec::new();
+    for worktree in worktrees {
+        if worktree.state != WorktreeState::Active
+            || is_main_worktree(repo_path, &worktree)
+            || default
+                .as_ref()
+                .is_some_and(|(name, _)| *name == worktree.branch)
+        {
+            continue;
+        }
+        let worktree_repo =
+            Repository::open(&worktree.path).context("failed to open worktree repo")?;
+
+        let mut reasons = Vec::new();
+        if local_branches.contains(&worktree.branch)
+            && let Some((default_name, default_tip)) = &default
+            && let Ok(tip) = repo.refname_to_id(&format!("refs/heads/{}", worktree.branch))
+            && (repo.graph_descendant_of(*default_tip, tip)?
+                || (tip == *default_tip && has_own_commits(&repo, &worktree.branch)))
+        {
+            reasons.push(StaleReason::Merged(default_name.clone()));
+        }
+        if let Some(upstream) = gone_upstream(&worktree_repo) {
+            reasons.push(StaleReason::UpstreamGone(upstream));
+        }
+        if let Some(idle) =
+            last_activity(&worktree_repo).and_then(|time| now.duration_since(time).ok())
+        {
+            let days = idle.as_secs() / (24 * 60 * 60);
+            if days >= idle_days {
+                reasons.push(StaleReason::Idle(days));
+            }
+        }
+
+        if !reasons.is_empty() {
+            stale.push(StaleWorktree { worktree, reasons });
+        }
+    }
+    Ok(stale)
+}
+
+/// Whether commits were made on `branch`, going by its reflog. A branch just
+/// created from the default branch has the same tip, but nothing of its own
+/// was merged.
+fn has_own_commits(repo: &Repository, branch: &str) -> bool {
+    repo.reflog(&format!("refs/heads/{branch}"))
+        .is_ok_and(|reflog| {
+            reflog.iter().any(|entry| {
+                entry
+                    .message()
+                    .is_some_and(|message| message.starts_with("commit"))
+            })
+        })
+}
+
+/// When a worktree was last worked on: the newest of its HEAD commit and the
+/// last write to its HEAD or index. Directory access times aren't used, since
+/// checking for changes reads every worktree.
+fn last_activity(repo: &Repository) -> Option<SystemTime> {
+    let commit = repo
+        .head()
+        .and_then(|head| head.peel_to_commit())
+        .ok()
+        .map(|commit| UNIX_EPOCH + Duration::from_secs(commit.time().seconds().max(0) as u64));
+    let mtime = |file: &str| {
+        fs::metadata(repo.path().join(file))
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    };
+    [commit, mtime("HEAD"), mtime("index")]
+        .into_iter()
+        .flatten()
+        .max()
+}
+
+/// The short name of HEAD's upstream if it is configured but missing
+fn gone_upstream(repo: &Repository) -> Option<String> {
+    let head = repo.head().ok().filter(|head| head.is_branch())?;
+    let upstream = repo.branch_upstream_name(head.name()?).ok()?;
+    let upstream = upstream.as_str()?;
+    if repo.find_reference(upstream).is_ok() {
+        return None;
+    }
+    Some(
+        upstream
+            .strip_prefix("refs/remotes/")
+            .unwrap_or(upstream)
+            .to_string(),
+    )
+}
+
+/// Worktrees share one stash, so worktrees updated in parallel take turns
+/// using it
+static STASH_LOCK: Mutex<()> = Mutex::new(());
+
+/// Options for `trees pull`
+pub struct PullOptions {
+    /// Number of remotes to fetch and worktrees to update at once
+    pub jobs: usize,
+    /// Rebase diverged branches onto their upstream instead of skipping them
+    pub rebase: bool,
+    /// Stash uncommitted changes around the update instead of skipping
+    pub autostash: bool,
+}
+
+/// What `trees pull` or `trees sync` did with one worktree
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpdateResult {
+    /// Moved to the commit it was updated to, having none of its own
+    FastForwarded,
+    Rebased,
+    Merged,
+    UpToDate,
+    /// The branch has commits its upstream doesn't, so it can't fast-forward
+    Diverged,
+    /// Detached, unborn, or without an upstream branch
+    NoUpstream,
+    /// Detached or unborn, so there is no branch to sync
+    NoBranch,
+    SkippedDirty,
+    /// Rebasing, merging or re-applying stashed changes conflicted, so the
+    /// worktree was put back exactly as it was
+    Conflict,
+}
+
+impl UpdateResult {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UpdateResult::FastForwarded => "fast-forwarded",
+            UpdateResult::Rebased => "rebased",
+            UpdateResult::Merged => "merged",
+            UpdateResult::UpToDate => "up-to-date",
+            UpdateResult::Diverged => "diverged",
+            UpdateResult::NoUpstream => "no-upstream",
+            UpdateResult::NoBranch => "no-branch",
+            UpdateResult::SkippedDirty => "skipped-dirty",
+            UpdateResult::Conflict => "conflict",
+        }
+    }
+}
+
+/// What happened to one worktree during `trees pull` or `trees sync`
+pub struct UpdateReport {
+    pub name: String,
+    pub branch: String,
+    pub result: Result<UpdateResult>,
+}
+
+impl UpdateReport {
+    /// Whether the update failed or conflicted. Skipped worktrees are fine.
+    pub fn failed(&self) -> bool {
+        !matches!(self.result, Ok(result) if result != UpdateResult::Conflict)
+    }
+}
+
+/// Fetch every remote, then bring each worktree's branch up to date with its
+/// upstream. Both run on up to `options.jobs` threads, with live progress on
+/// a terminal. Returns what happened to each worktree.
+pub fn pull_all_worktrees(repo_path: &str, options: &PullOptions) -> Result<Vec<UpdateReport>> {
+    fetch_all_remotes(repo_path, options.jobs, false)?;
+    info!("Fetched all remote branches");
+
+    // Dirty state is checked fresh in `pull_worktree`, so skip the cache
+    let worktrees: Vec<WorktreeInfo> = list_worktrees(repo_path)?
+        .into_iter()
+        .filter(|wt| wt.state.is_present())
+        .collect();
+    Ok(update_worktrees(worktrees, options.jobs, |worktree| {
+        pull_worktree(worktree, options)
+    }))
+}
+
+/// Run `update` on each of `worktrees` on up to `jobs` threads, with live
+/// progress on a terminal, and collect what happened to each
+fn update_worktrees(
+    worktrees: Vec<WorktreeInfo>,
+    jobs: usize,
+    update: impl Fn(&WorktreeInfo) -> Result<UpdateResult> + Sync,
+) -> Vec<UpdateReport> {
+    let progress = Progress::new(worktrees.iter().map(|wt| wt.name.clone()).collect());
+    let mut reports: Vec<Option<UpdateReport>> = worktrees.iter().map(|_| None).collect();
+    run_parallel(
+        worktrees.iter().enumerate().collect(),
+        jobs,
+        |(index, worktree)| {
+            progress.set(index, "updating");
+            let result = update(worktree);
+            match &result {
+                Ok(result) => progress.finish(index, result.as_str()),
+                Err(_) => progress.finish(index, "failed"),
+            }
+            (index, result)
+        },
+        |(index, result)| {
+            let worktree = &worktrees[index];
+            if let Err(e) = &result {
+                warning!("Failed to update worktree '{}': {e:#}", worktree.name);
+            }
+            reports[index] = Some(UpdateReport {
+                name: worktree.name.clone(),
+                branch: worktree.branch.clone(),
+                result,
+            });
+        },
+    );
+    reports.into_iter().flatten().collect()
+}
+
+/// Update one worktree's branch from its upstream. Without `options` this
+/// only fast-forwards, leaving diverged or dirty worktrees alone.
+fn pull_worktree(worktree: &WorktreeInfo, options: &PullOptions) -> Result<UpdateResult> {
+    let mut repo = Repository::open(&worktree.path).context("failed to open worktree repo")?;
+    let Some((local, remote, upstream_name)) = upstream_of_head(&repo) else {
+        return Ok(UpdateResult::NoUpstream);
+    };
+
+    let (ahead, behind) = repo
+        .graph_ahead_behind(local, remote)
+        .context("failed to compare with upstream")?;
+    if behind == 0 {
+        return Ok(UpdateResult::UpToDate);
+    }
+    if ahead > 0 && !options.rebase {
+        return Ok(UpdateResult::Diverged);
+    }
+    let is_dirty = is_worktree_dirty(&worktree.path)?;
+    if is_dirty && !options.autostash {
+        return Ok(UpdateResult::SkippedDirty);
+    }
+
+    // Rebasing happens in memory, so a conflict leaves nothing to undo
+    let (target, result) = if ahead > 0 {
+        match rebase_in_memory(&repo, local, remote)? {
+            Ok(rebased) => (rebased, UpdateResult::Rebased),
+            Err(_) => return Ok(UpdateResult::Conflict),
+        }
+    } else {
+        (remote, UpdateResult::FastForwarded)
+    };
+
+    let message = match result {
+        UpdateResult::Rebased => format!("pull --rebase: rebase onto {upstream_name}"),
+        _ => format!("pull: fast-forward to {upstream_name}"),
+    };
+    if !update_head_branch(
+        &mut repo, worktree, local, target, is_dirty, "pull", &message,
+    )? {
+        return Ok(UpdateResult::Conflict);
+    }
+    Ok(result)
+}
+
+/// Check out `target` in the worktree and point its branch at it, moving it
+/// from `local`. With `autostash`, local changes are stashed first and
+/// re-applied after. Returns `false` if that conflicts, after putting the
+/// worktree back exactly as it was.
+fn update_head_branch(
+    repo: &mut Repository,
+    worktree: &WorktreeInfo,
+    local: git2::Oid,
+    target: git2::Oid,
+    autostash: bool,
+    command: &str,
+    message: &str,
+) -> Result<bool> {
+    let branch_ref = repo
+        .head()
+        .ok()
+        .and_then(|head| head.name().map(str::to_string))
+        .context("failed to get head")?;
+    let stash = if autostash {
+        let signature = repo
+            .signature()
+            .context("set user.name and user.email to use --autostash")?;
+        let flags = git2::StashFlags::INCLUDE_UNTRACKED;
+        let message = format!("trees {command} --autostash on {branch_ref}");
+        let _lock = STASH_LOCK.lock().unwrap();
+        Some(
+            repo.stash_save(&signature, &message, Some(flags))
+                .context("failed to stash changes")?,
+        )
+    } else {
+        None
+    };
+
+    let updated =
+        move_branch(repo, &branch_ref, target, message, false).and_then(|()| match stash {
+            Some(stash) => apply_stash(repo, stash),
+            None => Ok(()),
+        });
+    if let Err(e) = updated {
+        verbose!("Rolling back '{}': {e:#}", worktree.name);
+        let message = format!("{command}: roll back");
+        move_branch(repo, &branch_ref, local, &message, true)?;
+        if let Some(stash) = stash {
+            apply_stash(repo, stash).with_context(|| {
+                format!("failed to restore changes, they are kept in the stash as {stash}")
+            })?;
+        }
+        return Ok(false);
+    }
+    Ok(true)
+}
+
+/// Options for `trees sync`
+pub struct SyncOptions {
+    /// Number of remotes to fetch and worktrees to update at once
+    pub jobs: usize,
+    /// Merge the default branch in instead of rebasing onto it
+    pub merge: bool,
+    /// Stash uncommitted changes around the update instead of skipping
+    pub autostash: bool,
+}
+
+/// Fetch every remote, then rebase each worktree's branch onto the default
+/// branch on `origin`, or merge it in with `options.merge`. The default
+/// branch's own worktrees are left to `trees pull`. Returns what happened to
+/// each worktree.
+pub fn sync_all_worktrees(repo_path: &str, options: &SyncOptions) -> Result<Vec<UpdateReport>> {
+    fetch_all_remotes(repo_path, options.jobs, false)?;
+    info!("Fetched all remote branches");
+
+    let repo = discover_repo(repo_path)?;
+    let (default_branch, _) =
+        default_branch(&repo).context("No default branch found (origin/HEAD, main or master)")?;
+    let upstream_name = format!("origin/{default_branch}");
+    let onto = repo
+        .refname_to_id(&format!("refs/remotes/{upstream_name}"))
+        .with_context(|| format!("No '{upstream_name}' to sync onto"))?;
+
+    let worktrees: Vec<WorktreeInfo> = list_worktrees(repo_path)?
+        .into_iter()
+        .filter(|wt| wt.state.is_present() && wt.branch != default_branch)
+        .collect();
+    Ok(update_worktrees(worktrees, options.jobs, |worktree| {
+        sync_worktree(worktree, onto, &upstream_name, options)
+    }))
+}
+
+/// Bring one worktree's branch up to date with `onto`, the commit of
+/// `upstream_name`
+fn sync_worktree(
+    worktree: &WorktreeInfo,
+    onto: git2::Oid,
+    upstream_name: &str,
+    options: &SyncOptions,
+) -> Result<UpdateResult> {
+    let mut repo = Repository::open(&worktree.path).context("failed to open worktree repo")?;
+    let Some(local) = repo
+        .head()
+        .ok()
+        .filter(|head| head.is_branch())
+        .and_then(|head| head.target())
+    else {
+        return Ok(UpdateResult::NoBranch);
+    };
+
+    let (ahead, behind) = repo
+        .graph_ahead_behind(local, onto)
+        .context("failed to compare with the default branch")?;
+    if behind == 0 {
+        return Ok(UpdateResult::UpToDate);
+    }
+    let is_dirty = is_worktree_dirty(&worktree.path)?;
+    if is_dirty && !options.autostash {
+        return Ok(UpdateResult::SkippedDirty);
+    }
+
+    // Rebasing and merging happen in memory, so a conflict leaves nothing to
+    // undo
+    let (target, result, message) = if ahead == 0 {
+        let message = format!("sync: fast-forward to {upstream_name}");
+        (onto, UpdateResult::FastForwarded, message)
+    } else if options.merge {
+        let Ok(mut index) = merge_in_memory(&repo, local, onto)? else {
+            return Ok(UpdateResult::Conflict);
+        };
+        let tree = index
+            .write_tree_to(&repo)
+            .and_then(|oid| repo.find_tree(oid))
+            .context("failed to write merged tree")?;
+        let signature = repo
+            .signature()
+            .context("set user.name and user.email to merge")?;
+        let parents = [repo.find_commit(local)?, repo.find_commit(onto)?];
+        let merged = repo
+            .commit(
+                None,
+                &signature,
+                &signature,
+                &format!(
+                    "Merge remote-tracking branch '{upstream_name}' into {}",
+                    worktree.branch
+                ),
+                &tree,
+                &[&parents[0], &parents[1]],
+            )
+            .context("failed to create merge commit")?;
+        let message = format!("sync: merge {upstream_name}");
+        (merged, UpdateResult::Merged, message)
+    } else {
+        match rebase_in_memory(&repo, local, onto)? {
+            Ok(rebased) => {
+                let message = format!("sync: rebase onto {upstream_name}");
+                (rebased, UpdateResult::Rebased, message)
+            }
+            Err(_) => return Ok(UpdateResult::Conflict),
+        }
+    };
+
+    if !update_head_branch(
+        &mut repo, worktree, local, target, is_dirty, "sync", &message,
+    )? {
+        return Ok(UpdateResult::Conflict);
+    }
+    Ok(result)
+}
+
+/// The commit HEAD's branch points at, its upstream's commit and the
+/// upstream's name. `None` if HEAD is detached or unborn or has no upstream.
+fn upstream_of_head(repo: &Repository) -> Option<(git2::Oid, git2::Oid, String)> {
+    let head = repo.head().ok().filter(|head| head.is_branch())?;
+    let branch = git2::Branch::wrap(head);
+    let upstream = branch.upstream().ok()?;
+    Some((
+        branch.get().target()?,
+        upstream.get().target()?,
+        upstream.name().ok().flatten()?.to_string(),
+    ))
+}
+
+/// Replay the commits in `local` that aren't in `upstream` on top of it,
+/// without touching the working tree. Returns the new tip, or the paths of
+/// the first commit that conflicts.
+fn rebase_in_memory(
+    repo: &Repository,
+    local: git2::Oid,
+    upstream: git2::Oid,
+) -> Result<std::result::Result<git2::Oid, Vec<String>>> {
+    let committer = repo
+        .signature()
+        .context("set user.name and user.email to rebase")?;
+    let branch = repo.find_annotated_commit(local)?;
+    let onto = repo.find_annotated_commit(upstream)?;
+    let mut options = git2::RebaseOptions::new();
+    options.inmemory(true);
+    let mut rebase = repo
+        .rebase(Some(&branch), Some(&onto), None, Some(&mut options))
+        .context("failed to start rebase")?;
+
+    let mut tip = upstream;
+    while let Some(operation) = rebase.next() {
+        operation.context("failed to apply commit")?;
+        let index = rebase.inmemory_index()?;
+        if index.has_conflicts() {
+            let paths = conflicting_paths(&index)?;
+            rebase.abort()?;
+            return Ok(Err(paths));
+        }
+        match rebase.commit(None, &committer, None) {
+            Ok(oid) => tip = oid,
+            // The change is already upstream, so the commit is dropped
+            Err(e) if e.code() == git2::ErrorCode::Applied => {}
+            Err(e) => return Err(e).context("failed to commit rebased change"),
+        }
+    }
+    rebase.finish(None).context("failed to finish rebase")?;
+    Ok(Ok(tip))
+}
+
+/// Check out `target` and point the branch `branch_ref` at it. With `force`,
+/// local changes and untracked files are discarded so the worktree matches
+/// `target` exactly.
+fn move_branch(
+    repo: &Repository,
+    branch_ref: &str,
+    target: git2::Oid,
+    message: &str,
+    force: bool,
+) -> Result<()> {
+    let commit = repo.find_commit(target).context("failed to find commit")?;
+    let mut checkout = git2::build::CheckoutBuilder::new();
+    if force {
+        repo.reset(commit.as_object(), git2::ResetType::Hard, None)
+            .context("failed to reset worktree")?;
+        checkout.force().remove_untracked(true);
+        repo.checkout_head(Some(&mut checkout))
+            .context("failed to clean worktree")?;
+    } else {
+        checkout.safe();
+        repo.checkout_tree(commit.as_object(), Some(&mut checkout))
+            .context("failed to check out commit")?;
+    }
+    repo.find_reference(branch_ref)
+        .and_then(|mut reference| reference.set_target(target, message))
+        .context("failed to update branch")?;
+    Ok(())
+}
+
+/// Re-apply the stash with id `stash`, including what was staged, and drop
+/// it. Fails without dropping it if applying conflicts.
+fn apply_stash(repo: &mut Repository, stash: git2::Oid) -> Result<()> {
+    let _lock = STASH_LOCK.lock().unwrap();
+    let mut index = None;
+    repo.stash_foreach(|i, _, oid| {
+        if *oid == stash {
+            index = Some(i);
+        }
+        index.is_none()
+    })?;
+    let index = index.context("stashed changes are gone")?;
+
+    let mut options = git2::StashApplyOptions::new();
+    options.reinstantiate_index();
+    repo.stash_apply(index, Some(&mut options))
+        .context("failed to re-apply stashed changes")?;
+    if repo.index()?.has_conflicts() {
+        anyhow::bail!("stashed changes conflict");
+    }
+    repo.stash_drop(index).context("failed to drop stash")?;
+    Ok(())
+}
+
+fn get_worktree_branch(worktree_path: &str) -> Result<String> {
+    let repo = Repository::open(worktree_path).context("failed to open worktree repo")?;
+    // Read HEAD without resolving it so unborn branches are reported too
+    let head = repo.find_reference("HEAD").context("failed to get head")?;
+    match head
+        .symbolic_target()
+        .and_then(|target| target.strip_prefix("refs/heads/"))
+    {
+        Some(branch) => Ok(branch.to_string()),
+        None => Ok("detached".to_string()),
+    }
+}
+
+/// Drop entries for worktrees other than `present` and save the cache
+fn save_dirty_cache(cache: &mut DirtyCache, present: &[(usize, String)]) {
+    let paths: Vec<&str> = present.iter().map(|(_, path)| path.as_str()).collect();
+    cache.retain_paths(&paths);
+    if let Err(e) = cache.save() {
+        verbose!("Failed to save dirty cache: {e}");
+    }
+}
+
+pub fn is_worktree_dirty(worktree_path: &str) -> Result<bool> {
+    Ok(get_change_counts(worktree_path)?.is_dirty())
+}
+
+/// Number of changed files in a worktree. A file with both staged and
+/// unstaged changes counts towards both.
+#[derive(Clone, Copy, Default)]
+pub struct ChangeCounts {
+    pub staged: usize,
+    pub unstaged: usize,
+    pub untracked: usize,
+}
+
+impl ChangeCounts {
+    pub fn is_dirty(&self) -> bool {
+        self.staged + self.unstaged + self.untracked > 0
+    }
+}
+
+pub fn get_change_counts(worktree_path: &str) -> Result<ChangeCounts> {
+    let repo = Repository::open(worktree_path).context("failed to open worktree repo")?;
+
+    // Check if there are any changes in the working directory
+    let mut options = git2::StatusOptions::new();
+    options.include_untracked(true);
+    options.include_ignored(false);
 
     let statuses = repo
         .statuses(Some(&mut options))
         .with_context(|| "Failed to get status")?;
 
-    Ok(!statuses.is_empty())
+    let staged_flags = git2::Status::INDEX_NEW
+        | git2::Status::INDEX_MODIFIED
+        | git2::Status::INDEX_DELETED
+        | git2::Status::INDEX_RENAMED
+        | git2::Status::INDEX_TYPECHANGE;
+    let unstaged_flags = git2::Status::WT_MODIFIED
+        | git2::Status::WT_DELETED
+        | git2::Status::WT_RENAMED
+        | git2::Status::WT_TYPECHANGE
+        | git2::Status::CONFLICTED;
+
+    let mut counts = ChangeCounts::default();
+    for entry in statuses.iter() {
+        let status = entry.status();
+        if status.intersects(staged_flags) {
+            counts.staged += 1;
+        }
+        if status.intersects(unstaged_flags) {
+            counts.unstaged += 1;
+        }
+        if status.contains(git2::Status::WT_NEW) {
+            counts.untracked += 1;
+        }
+    }
+    Ok(counts)
+}
+
+/// Everything `trees status` shows about one worktree
+pub struct WorktreeStatus {
+    pub details: BranchDetails,
+    pub changes: ChangeCounts,
+    /// Stashes made on this worktree's branch
+    pub stashes: usize,
+    /// Merge, rebase or similar operation in progress
+    pub operation: Option<&'static str>,
+    /// Commit time of HEAD, in seconds since the epoch
+    pub last_commit: Option<i64>,
+}
+
+pub fn get_worktree_status(worktree: &WorktreeInfo) -> Result<WorktreeStatus> {
+    let repo = Repository::open(&worktree.path).context("failed to open worktree repo")?;
+
+    let operation = match repo.state() {
+        git2::RepositoryState::Clean => None,
+        git2::RepositoryState::Merge => Some("merge"),
+        git2::RepositoryState::Revert | git2::RepositoryState::RevertSequence => Some("revert"),
+        git2::RepositoryState::CherryPick | git2::RepositoryState::CherryPickSequence => {
+            Some("cherry-pick")
+        }
+        git2::RepositoryState::Bisect => Some("bisect"),
+        git2::RepositoryState::Rebase
+        | git2::RepositoryState::RebaseInteractive
+        | git2::RepositoryState::RebaseMerge => Some("rebase"),
+        git2::RepositoryState::ApplyMailbox | git2::RepositoryState::ApplyMailboxOrRebase => {
+            Some("am")
+        }
+    };
+
+    // Stashes are shared by all worktrees; their messages record the branch
+    // they were made on ("WIP on <branch>: ..." or "On <branch>: ...")
+    let stashes = match repo.reflog("refs/stash") {
+        Ok(reflog) => reflog
+            .iter()
+            .filter(|entry| {
+                entry.message().is_some_and(|message| {
+                    message
+                        .strip_prefix("WIP on ")
+                        .or_else(|| message.strip_prefix("On "))
+                        .and_then(|rest| rest.split_once(':'))
+                        .is_some_and(|(branch, _)| branch == worktree.branch)
+                })
+            })
+            .count(),
+        Err(_) => 0,
+    };
+
+    let last_commit = repo
+        .head()
+        .and_then(|head| head.peel_to_commit())
+        .map(|commit| commit.time().seconds())
+        .ok();
+
+    Ok(WorktreeStatus {
+        details: get_branch_details(&worktree.path)?,
+        changes: get_change_counts(&worktree.path)?,
+        stashes,
+        operation,
+        last_commit,
+    })
 }
 
 pub fn get_worktree_commit_hash(worktree_path: &str) -> Result<String> {
@@ -447,18 +2586,65 @@ pub fn get_worktree_commit_hash(worktree_path: &str) -> Result<String> {
     Ok(commit.id().to_string()[..8].to_string()) // Return first 8 characters of commit hash
 }
 
+/// HEAD and upstream details of a worktree's checked-out branch
+#[derive(Default)]
+pub struct BranchDetails {
+    /// Full object id of HEAD, if HEAD points at a commit
+    pub head: Option<String>,
+    /// Upstream branch, e.g. `origin/main`
+    pub upstream: Option<String>,
+    /// Commits ahead of and behind the upstream
+    pub ahead_behind: Option<(usize, usize)>,
+}
+
+pub fn get_branch_details(worktree_path: &str) -> Result<BranchDetails> {
+    let repo = Repository::open(worktree_path).context("failed to open worktree repo")?;
+    let mut details = BranchDetails::default();
+    let Ok(head) = repo.head() else {
+        // Unborn branch, nothing to report yet
+        return Ok(details);
+    };
+    let head_oid = head.target();
+    details.head = head_oid.map(|oid| oid.to_string());
+
+    if head.is_branch()
+        && let Ok(upstream) = git2::Branch::wrap(head).upstream()
+    {
+        details.upstream = upstream.name().ok().flatten().map(str::to_string);
+        if let (Some(local), Some(remote)) = (head_oid, upstream.get().target()) {
+            details.ahead_behind = repo.graph_ahead_behind(local, remote).ok();
+        }
+    }
+    Ok(details)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use std::fs;
+    use std::process::Command;
     use tempfile::tempdir;
 
+    #[test]
+    fn test_repo_name_from_url() {
+        assert_eq!(
+            repo_name_from_url("https://github.com/jacbart/trees.git").unwrap(),
+            "trees"
+        );
+        assert_eq!(
+            repo_name_from_url("git@github.com:jacbart/trees").unwrap(),
+            "trees"
+        );
+        assert_eq!(repo_name_from_url("file:///srv/repo/").unwrap(), "repo");
+        assert_eq!(repo_name_from_url("host:repo.git").unwrap(), "repo");
+        assert!(repo_name_from_url("/").is_err());
+    }
+
     #[test]
     fn test_get_worktree_branch_head_branch() {
         let dir = tempdir().unwrap();
-        let git_dir = dir.path().join(".git");
-        fs::create_dir_all(&git_dir).unwrap();
-        let head_path = git_dir.join("HEAD");
+        Repository::init(dir.path()).unwrap();
+        let head_path = dir.path().join(".git").join("HEAD");
         fs::write(&head_path, "ref: refs/heads/feature/test\n").unwrap();
         let branch = get_worktree_branch(dir.path().to_str().unwrap()).unwrap();
         assert_eq!(branch, "feature/test");
@@ -467,9 +2653,8 @@ mod tests {
     #[test]
     fn test_get_worktree_branch_head_detached() {
         let dir = tempdir().unwrap();
-        let git_dir = dir.path().join(".git");
-        fs::create_dir_all(&git_dir).unwrap();
-        let head_path = git_dir.join("HEAD");
+        Repository::init(dir.path()).unwrap();
+        let head_path = dir.path().join(".git").join("HEAD");
         fs::write(&head_path, "e1e1e1e1e1e1e1e1e1e1e1e1e1e1e1e1e1e1e1e1\n").unwrap();
         let branch = get_worktree_branch(dir.path().to_str().unwrap()).unwrap();
         assert_eq!(branch, "detached");
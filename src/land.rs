@@ -0,0 +1,301 @@
+//! `trees land`: bring a feature worktree into the default branch and clean
+//! up after it.
+//!
+//! Landing runs five steps: update the worktrees, merge the feature into the
+//! default branch's worktree, push the default branch, remove the feature
+//! worktree and delete its branch. Before each step runs, it is recorded in
+//! `trees-land.json` in the common git directory. When a step fails the
+//! record stays behind, so `trees land --continue` starts again at that step
+//! once the problem is fixed, and `trees land --abort` forgets it.
+
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::cli::MergeStrategy;
+use crate::git::{
+    MergeOptions, MergeOutcome, PullOptions, UpdateResult, WorktreeInfo, WorktreeState,
+    branch_exists, common_dir, default_branch_name, delete_branch, is_main_worktree,
+    is_worktree_dirty, list_worktrees, merge_worktrees, pull_all_worktrees, push_branch,
+    remove_worktree,
+};
+use crate::report::info;
+
+const STATE_FILE: &str = "trees-land.json";
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum Step {
+    Update,
+    Merge,
+    Push,
+    Remove,
+    DeleteBranch,
+}
+
+impl Step {
+    const ALL: [Step; 5] = [
+        Step::Update,
+        Step::Merge,
+        Step::Push,
+        Step::Remove,
+        Step::DeleteBranch,
+    ];
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Step::Update => "update",
+            Step::Merge => "merge",
+            Step::Push => "push",
+            Step::Remove => "remove",
+            Step::DeleteBranch => "delete-branch",
+        }
+    }
+
+    fn number(self) -> usize {
+        Step::ALL.iter().position(|step| *step == self).unwrap_or(0) + 1
+    }
+
+    fn next(self) -> Option<Step> {
+        Step::ALL.get(self.number()).copied()
+    }
+}
+
+/// A landing in progress, as saved between steps
+#[derive(Serialize, Deserialize)]
+struct LandState {
+    /// The worktree being landed and its branch
+    worktree: String,
+    branch: String,
+    /// The default branch's worktree, its branch and path
+    target: String,
+    target_branch: String,
+    target_path: String,
+    /// Merge strategy, as given on the command line
+    strategy: String,
+    delete_remote: bool,
+    /// The step to run next
+    step: Step,
+}
+
+/// Options for `trees land`
+pub struct LandOptions {
+    pub strategy: MergeStrategy,
+    /// Delete the branch on its remote too
+    pub delete_remote: bool,
+    /// Number of remotes to fetch and worktrees to update at once
+    pub jobs: usize,
+}
+
+/// Land `worktree` on the default branch. Returns the path of the default
+/// branch's worktree.
+pub fn land(repo_path: &str, worktree: &WorktreeInfo, options: &LandOptions) -> Result<String> {
+    let file = state_file(repo_path)?;
+    if let Some(state) = load(&file)? {
+        anyhow::bail!(
+            "Already landing '{}'; run `trees land --continue` to finish it or \
+             `trees land --abort` to give up",
+            state.worktree
+        );
+    }
+
+    let default_branch = default_branch_name(repo_path)?
+        .context("No default branch found (origin/HEAD, main or master)")?;
+    let worktrees = list_worktrees(repo_path)?;
+    let target = worktrees
+        .iter()
+        .find(|wt| wt.branch == default_branch && wt.state.is_present())
+        .with_context(|| format!("No worktree has the default branch '{default_branch}'"))?;
+    anyhow::ensure!(
+        worktree.name != target.name,
+        "'{}' is the default branch's worktree; pick a feature worktree to land",
+        worktree.name
+    );
+    anyhow::ensure!(
+        !is_main_worktree(repo_path, worktree),
+        "'{}' is the main worktree and can't be removed",
+        worktree.name
+    );
+    anyhow::ensure!(
+        worktree.state == WorktreeState::Active,
+        "'{}' is {}",
+        worktree.name,
+        worktree.state.as_str()
+    );
+    anyhow::ensure!(
+        worktree.branch != "detached" && worktree.branch != "unknown",
+        "'{}' is not on a branch",
+        worktree.name
+    );
+    for checked in [worktree, target] {
+        anyhow::ensure!(
+            !is_worktree_dirty(&checked.path)?,
+            "'{}' has uncommitted changes; commit or stash them first",
+            checked.name
+        );
+    }
+
+    let mut state = LandState {
+        worktree: worktree.name.clone(),
+        branch: worktree.branch.clone(),
+        target: target.name.clone(),
+        target_branch: target.branch.clone(),
+        target_path: target.path.clone(),
+        strategy: options.strategy.as_str().to_string(),
+        delete_remote: options.delete_remote,
+        step: Step::Update,
+    };
+    run(repo_path, &file, &mut state, options.jobs)
+}
+
+/// Continue the landing in progress from the step that failed. Returns the
+/// path of the default branch's worktree.
+pub fn resume(repo_path: &str, jobs: usize) -> Result<String> {
+    let file = state_file(repo_path)?;
+    let mut state = load(&file)?.context("No landing in progress")?;
+    info!(
+        "Continuing to land '{}' at step '{}'",
+        state.worktree,
+        state.step.as_str()
+    );
+    run(repo_path, &file, &mut state, jobs)
+}
+
+/// Forget the landing in progress. Steps that already ran are not undone.
+pub fn abort(repo_path: &str) -> Result<()> {
+    let file = state_file(repo_path)?;
+    let state = load(&file)?.context("No landing in progress")?;
+    fs::remove_file(&file).with_context(|| format!("Failed to remove {}", file.display()))?;
+    info!(
+        "Stopped landing '{}' before step '{}'; earlier steps are kept",
+        state.worktree,
+        state.step.as_str()
+    );
+    Ok(())
+}
+
+fn run(repo_path: &str, file: &Path, state: &mut LandState, jobs: usize) -> Result<String> {
+    loop {
+        save(file, state)?;
+        let step = state.step;
+        info!(
+            "[{}/{}] {}",
+            step.number(),
+            Step::ALL.len(),
+            describe(step, state)
+        );
+        run_step(repo_path, state, jobs).with_context(|| {
+            format!(
+                "Landing '{}' stopped at step '{}'. Fix the problem and run \
+                 `trees land --continue`, or `trees land --abort` to give up",
+                state.worktree,
+                step.as_str()
+            )
+        })?;
+        match step.next() {
+            Some(next) => state.step = next,
+            None => break,
+        }
+    }
+    fs::remove_file(file).with_context(|| format!("Failed to remove {}", file.display()))?;
+    info!("Landed '{}' on '{}'", state.branch, state.target_branch);
+    Ok(state.target_path.clone())
+}
+
+fn describe(step: Step, state: &LandState) -> String {
+    match step {
+        Step::Update => "Updating worktrees".to_string(),
+        Step::Merge => format!(
+            "Merging '{}' into '{}' with {}",
+            state.branch, state.target_branch, state.strategy
+        ),
+        Step::Push => format!("Pushing '{}'", state.target_branch),
+        Step::Remove => format!("Removing worktree '{}'", state.worktree),
+        Step::DeleteBranch => format!("Deleting branch '{}'", state.branch),
+    }
+}
+
+fn run_step(repo_path: &str, state: &LandState, jobs: usize) -> Result<()> {
+    match state.step {
+        Step::Update => {
+            let options = PullOptions {
+                jobs,
+                rebase: false,
+                autostash: false,
+            };
+            let reports = pull_all_worktrees(repo_path, &options)?;
+            let report = reports
+                .into_iter()
+                .find(|report| report.name == state.target)
+                .with_context(|| format!("'{}' was not updated", state.target))?;
+            match report.result? {
+                UpdateResult::FastForwarded | UpdateResult::UpToDate | UpdateResult::NoUpstream => {
+                }
+                result => anyhow::bail!(
+                    "Could not update '{}' from its upstream: {}",
+                    state.target,
+                    result.as_str()
+                ),
+            }
+        }
+        Step::Merge => {
+            let strategy = MergeStrategy::from_str(&state.strategy, false)
+                .map_err(|e| anyhow::anyhow!("Invalid merge strategy: {e}"))?;
+            let options = MergeOptions {
+                strategy,
+                message: None,
+                dry_run: false,
+            };
+            if let MergeOutcome::Conflict(paths) =
+                merge_worktrees(repo_path, &state.worktree, &state.target, &options)?
+            {
+                anyhow::bail!(
+                    "Merging conflicts in {}; resolve them with `trees merge` or by updating \
+                     '{}'",
+                    paths.join(", "),
+                    state.branch
+                );
+            }
+        }
+        Step::Push => {
+            if push_branch(repo_path, &state.target_branch)?.is_none() {
+                info!("'{}' has no upstream, not pushing", state.target_branch);
+            }
+        }
+        Step::Remove => {
+            // Already gone when continuing after the removal itself went through
+            if list_worktrees(repo_path)?
+                .iter()
+                .any(|wt| wt.name == state.worktree)
+            {
+                remove_worktree(repo_path, &state.worktree, false)?;
+            }
+        }
+        Step::DeleteBranch => {
+            if branch_exists(repo_path, &state.branch)? {
+                delete_branch(repo_path, &state.branch, state.delete_remote)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn state_file(repo_path: &str) -> Result<PathBuf> {
+    Ok(common_dir(repo_path)?.join(STATE_FILE))
+}
+
+fn load(file: &Path) -> Result<Option<LandState>> {
+    match fs::read(file) {
+        Ok(contents) => serde_json::from_slice(&contents)
+            .map(Some)
+            .with_context(|| format!("Failed to read {}", file.display())),
+        Err(_) => Ok(None),
+    }
+}
+
+fn save(file: &Path, state: &LandState) -> Result<()> {
+    fs::write(file, serde_json::to_vec_pretty(state)?)
+        .with_context(|| format!("Failed to write {}", file.display()))
+}
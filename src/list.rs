@@ -0,0 +1,126 @@
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::git::{BranchDetails, WorktreeInfo, WorktreeState, get_branch_details};
+
+/// Version of the `list --format json|porcelain` output. Bump it whenever a
+/// field is removed or changes meaning; adding fields is backwards compatible.
+pub const LIST_FORMAT_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+struct ListOutput<'a> {
+    version: u32,
+    worktrees: Vec<ListEntry<'a>>,
+}
+
+/// One worktree in machine-readable `list` output
+#[derive(Serialize)]
+struct ListEntry<'a> {
+    name: &'a str,
+    path: &'a str,
+    /// Checked-out branch, `None` when HEAD is detached or unreadable
+    branch: Option<&'a str>,
+    detached: bool,
+    is_dirty: bool,
+    is_current: bool,
+    state: &'static str,
+    lock_reason: Option<&'a str>,
+    head: Option<String>,
+    upstream: Option<String>,
+    ahead: Option<usize>,
+    behind: Option<usize>,
+}
+
+impl<'a> ListEntry<'a> {
+    fn new(worktree: &'a WorktreeInfo, current_path: Option<&str>) -> Self {
+        let details = if worktree.state.is_present() {
+            get_branch_details(&worktree.path).unwrap_or_default()
+        } else {
+            BranchDetails::default()
+        };
+        let branch = match worktree.branch.as_str() {
+            "detached" | "unknown" => None,
+            branch => Some(branch),
+        };
+        let lock_reason = match &worktree.state {
+            WorktreeState::Locked(reason) => reason.as_deref(),
+            _ => None,
+        };
+        ListEntry {
+            name: &worktree.name,
+            path: &worktree.path,
+            branch,
+            detached: worktree.branch == "detached",
+            is_dirty: worktree.is_dirty,
+            is_current: current_path == Some(worktree.path.as_str()),
+            state: worktree.state.as_str(),
+            lock_reason,
+            head: details.head,
+            upstream: details.upstream,
+            ahead: details.ahead_behind.map(|(ahead, _)| ahead),
+            behind: details.ahead_behind.map(|(_, behind)| behind),
+        }
+    }
+}
+
+/// Path of the worktree containing `path`, preferring the innermost one when
+/// worktrees are nested
+pub fn current_worktree_path<'a>(worktrees: &'a [WorktreeInfo], path: &str) -> Option<&'a str> {
+    let path = std::fs::canonicalize(path).ok()?;
+    worktrees
+        .iter()
+        .filter(|wt| path.starts_with(&wt.path))
+        .max_by_key(|wt| wt.path.len())
+        .map(|wt| wt.path.as_str())
+}
+
+pub fn print_json(worktrees: &[WorktreeInfo], current_path: Option<&str>) -> Result<()> {
+    let output = ListOutput {
+        version: LIST_FORMAT_VERSION,
+        worktrees: worktrees
+            .iter()
+            .map(|wt| ListEntry::new(wt, current_path))
+            .collect(),
+    };
+    println!("{}", serde_json::to_string_pretty(&output)?);
+    Ok(())
+}
+
+/// Print worktrees in the style of `git worktree list --porcelain`: a
+/// `version` record followed by one blank-line-terminated record per worktree
+pub fn print_porcelain(worktrees: &[WorktreeInfo], current_path: Option<&str>) {
+    println!("version {LIST_FORMAT_VERSION}");
+    println!();
+    for worktree in worktrees {
+        let entry = ListEntry::new(worktree, current_path);
+        println!("worktree {}", entry.path);
+        println!("name {}", entry.name);
+        if let Some(head) = &entry.head {
+            println!("HEAD {head}");
+        }
+        match entry.branch {
+            Some(branch) => println!("branch refs/heads/{branch}"),
+            None if entry.detached => println!("detached"),
+            None => {}
+        }
+        if let Some(upstream) = &entry.upstream {
+            println!("upstream {upstream}");
+        }
+        if let (Some(ahead), Some(behind)) = (entry.ahead, entry.behind) {
+            println!("ahead {ahead}");
+            println!("behind {behind}");
+        }
+        if entry.is_dirty {
+            println!("dirty");
+        }
+        match &worktree.state {
+            WorktreeState::Active => {}
+            WorktreeState::Locked(Some(reason)) => println!("locked {reason}"),
+            state => println!("{}", state.as_str()),
+        }
+        if entry.is_current {
+            println!("current");
+        }
+        println!();
+    }
+}
@@ -1,5 +1,13 @@
+mod auth;
+mod cache;
 mod cli;
 mod git;
+mod land;
+mod list;
+mod progress;
+mod report;
+mod resolve;
+mod status;
 mod tui;
 
 use anyhow::{Result, ensure};
@@ -7,9 +15,17 @@ use clap::Parser;
 use std::path::Path;
 
 use crate::git::{
-    change_directory, create_worktree, get_branches, get_worktree_commit_hash, get_worktrees,
-    merge_worktrees, pull_all_worktrees, remove_worktree,
+    CreateOptions, MergeOptions, MergeOutcome, PullOptions, SyncOptions, UpdateReport,
+    WorktreeInfo, WorktreeState, cached_dirty_states, clone_bare, convert_to_bare, create_worktree,
+    default_branch_name, default_jobs, delete_branch, fetch_all_remotes, find_stale_worktrees,
+    find_worktree_target, get_branches, get_config_bool, get_removal_risks,
+    get_worktree_commit_hash, get_worktrees, is_branch_merged, is_main_worktree, list_worktrees,
+    local_branch_for_remote, merge_in_progress, merge_worktrees, predict_conflicts,
+    prune_worktrees, pull_all_worktrees, remove_worktree, sync_all_worktrees,
+    worktrees_with_gone_upstream,
 };
+use crate::land::LandOptions;
+use crate::report::{info, verbose};
 use crate::tui::TuiSelector;
 
 const ZSH_SCRIPT: &str = r#"# Trees zsh integration
@@ -20,14 +36,14 @@ trees() {
     local STATUS
     if [ $# -gt 0 ]; then
         case "$1" in
-            add|rm|merge|pull|list|--help|-h|help|--version|-V|--dir-only)
-                # These commands don't need --dir-only, run directly
+            rm|merge|pull|list|status|fetch|prune|clean|conflicts|sync|shell|--help|-h|help|--version|-V|--dir-only)
+                # These commands don't print a directory, run directly
                 trees-bin "$@"
                 STATUS=$?
                 return $STATUS
                 ;;
             *)
-                # For other cases (like no args), try to get directory
+                # Other commands (add, clone, ...) print the directory to cd to
                 DIR=$(trees-bin "$@" --dir-only)
                 STATUS=$?
                 ;;
@@ -56,10 +72,19 @@ _trees() {
         cmds)
             _values 'trees commands' \
                 'add[Add a new worktree]' \
+                'clone[Clone a repository into a bare worktree layout]' \
+                'convert[Convert a clone into a bare worktree layout]' \
                 'list[List worktrees]' \
+                'status[Show status of all worktrees]' \
                 'rm[Remove a worktree]' \
                 'merge[Merge two worktrees]' \
-                'pull[Pull updates for all worktrees]'
+                'prune[Clean up worktrees whose directory is gone]' \
+                'clean[Find stale worktrees and pick which to remove]' \
+                'land[Merge a worktree into the default branch and clean up]' \
+                'conflicts[Predict which worktrees would conflict when merged]' \
+                'fetch[Fetch all remotes and report changed branches]' \
+                'pull[Pull updates for all worktrees]' \
+                'sync[Rebase all worktrees onto the default branch]'
             ;;
         args)
             case "$line[1]" in
@@ -82,14 +107,14 @@ trees() {
     local STATUS
     if [ $# -gt 0 ]; then
         case "$1" in
-            add|rm|merge|pull|list|--help|-h|help|--version|-V|--dir-only)
-                # These commands don't need --dir-only, run directly
+            rm|merge|pull|list|status|fetch|prune|clean|conflicts|sync|shell|--help|-h|help|--version|-V|--dir-only)
+                # These commands don't print a directory, run directly
                 trees-bin "$@"
                 STATUS=$?
                 return $STATUS
                 ;;
             *)
-                # For other cases (like no args), try to get directory
+                # Other commands (add, clone, ...) print the directory to cd to
                 DIR=$(trees-bin "$@" --dir-only)
                 STATUS=$?
                 ;;
@@ -112,7 +137,7 @@ _trees_completion() {
     cur="${COMP_WORDS[COMP_CWORD]}"
     prev="${COMP_WORDS[COMP_CWORD-1]}"
     
-    opts="add list rm merge pull"
+    opts="add list status rm prune clean merge land conflicts fetch pull sync clone convert"
     
     if [[ ${cur} == * ]] ; then
         COMPREPLY=( $(compgen -W "${opts}" -- ${cur}) )
@@ -129,13 +154,13 @@ function trees
     set STATUS
     if [ $argv[1] ]
         switch $argv[1]
-            case "add" "rm" "merge" "pull" "list" "--help" "-h" "help" "--version" "-V" "--dir-only"
-                # These commands don't need --dir-only, run directly
+            case "rm" "merge" "pull" "list" "status" "fetch" "prune" "clean" "conflicts" "sync" "shell" "--help" "-h" "help" "--version" "-V" "--dir-only"
+                # These commands don't print a directory, run directly
                 trees-bin $argv
                 set STATUS $status
                 return $STATUS
             case "*"
-                # For other cases (like no args), try to get directory
+                # Other commands (add, clone, ...) print the directory to cd to
                 set DIR (trees-bin $argv --dir-only)
                 set STATUS $status
         end
@@ -151,30 +176,55 @@ function trees
 end
 
 # Add completion for trees
-complete -c trees -f -a "add list rm merge pull" -d "Git worktree management"
+complete -c trees -f -a "add list status rm prune clean merge land conflicts fetch pull sync clone convert" -d "Git worktree management"
 complete -c trees -n "__fish_seen_subcommand_from add" -f -d "Add a new worktree"
 complete -c trees -n "__fish_seen_subcommand_from list" -f -d "List worktrees"
+complete -c trees -n "__fish_seen_subcommand_from status" -f -d "Show status of all worktrees"
 complete -c trees -n "__fish_seen_subcommand_from rm" -f -d "Remove a worktree"
 complete -c trees -n "__fish_seen_subcommand_from merge" -f -d "Merge two worktrees"
-complete -c trees -n "__fish_seen_subcommand_from pull" -f -d "Pull updates for all worktrees""#;
+complete -c trees -n "__fish_seen_subcommand_from prune" -f -d "Clean up worktrees whose directory is gone"
+complete -c trees -n "__fish_seen_subcommand_from clean" -f -d "Find stale worktrees and pick which to remove"
+complete -c trees -n "__fish_seen_subcommand_from land" -f -d "Merge a worktree into the default branch and clean up"
+complete -c trees -n "__fish_seen_subcommand_from conflicts" -f -d "Predict which worktrees would conflict when merged"
+complete -c trees -n "__fish_seen_subcommand_from fetch" -f -d "Fetch all remotes and report changed branches"
+complete -c trees -n "__fish_seen_subcommand_from pull" -f -d "Pull updates for all worktrees"
+complete -c trees -n "__fish_seen_subcommand_from sync" -f -d "Rebase all worktrees onto the default branch"
+complete -c trees -n "__fish_seen_subcommand_from clone" -f -d "Clone a repository into a bare worktree layout"
+complete -c trees -n "__fish_seen_subcommand_from convert" -f -d "Convert a clone into a bare worktree layout""#;
 
 fn main() -> Result<()> {
     let cli = cli::Cli::parse();
     let path_arg = &cli.config.path;
 
+    report::set_verbosity(if cli.config.quiet {
+        report::Verbosity::Quiet
+    } else if cli.config.verbose {
+        report::Verbosity::Verbose
+    } else {
+        report::Verbosity::Normal
+    });
+
     ensure!(
         Path::new(path_arg).exists(),
         "need an existing repo, set --path or cd to git repo"
     );
 
     match &cli.command {
-        Some(cli::Commands::List) => {
+        Some(cli::Commands::List { format }) => {
             let worktrees = get_worktrees(path_arg)?;
+            let current_path = list::current_worktree_path(&worktrees, path_arg);
 
-            if worktrees.is_empty() {
-                if !cli.config.dir_only {
-                    println!("No worktrees found");
+            match format {
+                cli::ListFormat::Json => return list::print_json(&worktrees, current_path),
+                cli::ListFormat::Porcelain => {
+                    list::print_porcelain(&worktrees, current_path);
+                    return Ok(());
                 }
+                cli::ListFormat::Text => {}
+            }
+
+            if worktrees.is_empty() {
+                info!("No worktrees found");
                 return Ok(());
             }
 
@@ -194,138 +244,367 @@ fn main() -> Result<()> {
                     } else {
                         format!(" [{}]", worktree.branch)
                     };
-                    println!("{} {} {}", worktree.path, commit_hash, branch_display);
+                    let state_display = match worktree.state {
+                        WorktreeState::Active => "".to_string(),
+                        ref state => format!(" {}", state.as_str()),
+                    };
+                    println!(
+                        "{} {}{}{}",
+                        worktree.path, commit_hash, branch_display, state_display
+                    );
                 }
             }
         }
-        Some(cli::Commands::Add) => {
-            // Use TUI to select or create branch
-            let (local_branches, remote_branches) = get_branches(path_arg)?;
+        Some(cli::Commands::Status) => {
+            let worktrees = get_worktrees(path_arg)?;
+            status::print_status(&worktrees);
+        }
+        Some(cli::Commands::Add {
+            branch,
+            base,
+            track,
+            dir,
+        }) => {
+            let mut track = track.clone();
+            let branch_name = if let Some(branch) = branch {
+                branch.clone()
+            } else {
+                // Use TUI to select or create branch
+                let (local_branches, remote_branches) = get_branches(path_arg)?;
 
-            let mut all_branches = Vec::new();
-            all_branches.extend(local_branches.iter().map(|b| format!("local: {b}")));
-            all_branches.extend(remote_branches.iter().map(|b| format!("remote: {b}")));
-            all_branches.push("Create new branch".to_string());
+                let mut all_branches = Vec::new();
+                all_branches.extend(local_branches.iter().map(|b| format!("local: {b}")));
+                all_branches.extend(remote_branches.iter().map(|b| format!("remote: {b}")));
+                all_branches.push("Create new branch".to_string());
+
+                let Some(selected) = TuiSelector::select_branch(&all_branches)? else {
+                    info!("No branch selected, exiting");
+                    return Ok(());
+                };
 
-            if let Some(selected) = TuiSelector::select_branch(&all_branches)? {
-                let branch_name = if selected == "Create new branch" {
+                if selected == "Create new branch" {
                     if let Some(new_branch) = TuiSelector::create_new_branch()? {
-                        if !cli.config.dir_only {
-                            println!("Creating new branch: {new_branch}");
-                        }
+                        info!("Creating new branch: {new_branch}");
                         new_branch
                     } else {
-                        if !cli.config.dir_only {
-                            println!("No branch name provided, exiting");
-                        }
+                        info!("No branch name provided, exiting");
                         return Ok(());
                     }
                 } else {
                     // Extract branch name from selection
-                    let branch_name = selected.split(": ").nth(1).unwrap_or(&selected).to_string();
-                    if !cli.config.dir_only {
-                        println!("Selected branch: {branch_name}");
-                    }
+                    let (branch_name, remote_branch) = branch_from_selection(path_arg, &selected)?;
+                    verbose!("Selected branch: {branch_name}");
+                    track = remote_branch;
                     branch_name
-                };
-
-                if !cli.config.dir_only {
-                    println!("Creating worktree for branch: {branch_name}");
                 }
-                let worktree_name = create_worktree(path_arg, &branch_name)?;
+            };
 
-                // Get the worktree path and change to it
-                let worktrees = get_worktrees(path_arg)?;
-                if let Some(worktree) = worktrees.iter().find(|wt| wt.name == worktree_name) {
-                    if cli.config.dir_only {
-                        println!("{}", worktree.path);
-                    } else {
-                        change_directory(&worktree.path)?;
-                    }
-                }
-            } else {
-                if !cli.config.dir_only {
-                    println!("No branch selected, exiting");
-                }
+            info!("Creating worktree for branch: {branch_name}");
+            let options = CreateOptions {
+                base: base.clone(),
+                track,
+                dir: dir.clone(),
+            };
+            let worktree_path = create_worktree(path_arg, &branch_name, &options)?;
+            println!("{worktree_path}");
+        }
+        Some(cli::Commands::Clone { url, name }) => {
+            let worktree_path = clone_bare(url, path_arg, name.as_deref())?;
+            println!("{worktree_path}");
+        }
+        Some(cli::Commands::Convert { dry_run }) => {
+            let worktree_path = convert_to_bare(path_arg, *dry_run)?;
+
+            if !*dry_run {
+                println!("{worktree_path}");
             }
         }
-        Some(cli::Commands::Rm) => {
+        Some(cli::Commands::Rm {
+            targets,
+            force,
+            yes,
+            unlock,
+            delete_branch,
+            keep_branch,
+            delete_remote,
+        }) => {
             let worktrees = get_worktrees(path_arg)?;
 
             if worktrees.is_empty() {
-                println!("No worktrees found");
+                info!("No worktrees found");
                 return Ok(());
             }
 
-            let worktree_names: Vec<String> = worktrees
-                .iter()
-                .map(|wt| {
-                    let status = if wt.is_dirty { " (dirty)" } else { "" };
-                    format!("{} -> {} ({}){}", wt.name, wt.path, wt.branch, status)
-                })
-                .collect();
-
-            if let Some(selected) = TuiSelector::select_worktree(&worktree_names)? {
-                // Extract worktree name from selection
-                if let Some(worktree_name) = selected.split(" -> ").next() {
-                    if let Some(worktree) = worktrees.iter().find(|wt| wt.name == worktree_name) {
-                        if worktree.is_dirty && !TuiSelector::confirm_deletion(worktree_name)? {
-                            println!("Deletion cancelled");
-                            return Ok(());
-                        }
-                        remove_worktree(path_arg, worktree_name)?;
+            let delete_branch = *delete_branch
+                || *delete_remote
+                || (!*keep_branch
+                    && get_config_bool(path_arg, "trees.deleteBranch").unwrap_or(false));
+            let options = RemoveOptions {
+                force: *force,
+                yes: *yes,
+                unlock: *unlock,
+                delete_branch,
+                delete_remote: *delete_remote,
+            };
+            if targets.is_empty() {
+                let worktree_names: Vec<String> = worktrees.iter().map(worktree_option).collect();
+
+                if let Some(selected) = TuiSelector::select_worktree(&worktree_names)? {
+                    // Extract worktree name from selection
+                    if let Some(worktree_name) = selected.split(" -> ").next()
+                        && let Some(worktree) = worktrees.iter().find(|wt| wt.name == worktree_name)
+                    {
+                        remove_selected(path_arg, &[worktree], &options, true)?;
+                    }
+                }
+            } else {
+                let mut selected = Vec::new();
+                for target in targets {
+                    let worktree = find_worktree_target(&worktrees, target)
+                        .ok_or_else(|| anyhow::anyhow!("No worktree matches '{target}'"))?;
+                    if !selected
+                        .iter()
+                        .any(|wt: &&WorktreeInfo| wt.name == worktree.name)
+                    {
+                        selected.push(worktree);
                     }
                 }
+                remove_selected(path_arg, &selected, &options, false)?;
             }
         }
-        Some(cli::Commands::Merge) => {
+        Some(cli::Commands::Prune) => {
+            let pruned = prune_worktrees(path_arg)?;
+            if pruned.is_empty() {
+                info!("Nothing to prune");
+            }
+            for worktree in pruned {
+                info!("Pruned worktree '{}' ({})", worktree.name, worktree.path);
+            }
+        }
+        Some(cli::Commands::Clean {
+            days,
+            yes,
+            force,
+            dry_run,
+            delete_branch,
+            keep_branch,
+        }) => {
+            let stale = find_stale_worktrees(path_arg, get_worktrees(path_arg)?, *days)?;
+            if stale.is_empty() {
+                info!("No stale worktrees found");
+                return Ok(());
+            }
+            if *dry_run {
+                status::print_stale_worktrees(&stale);
+                return Ok(());
+            }
+
+            let options = RemoveOptions {
+                force: *force,
+                yes: *yes,
+                unlock: false,
+                delete_branch: *delete_branch
+                    || (!*keep_branch
+                        && get_config_bool(path_arg, "trees.deleteBranch").unwrap_or(false)),
+                delete_remote: false,
+            };
+            if *yes {
+                let worktrees: Vec<&WorktreeInfo> =
+                    stale.iter().map(|candidate| &candidate.worktree).collect();
+                remove_selected(path_arg, &worktrees, &options, false)?;
+            } else {
+                let items: Vec<String> = stale
+                    .iter()
+                    .map(|candidate| {
+                        format!(
+                            "{} [{}]",
+                            worktree_option(&candidate.worktree),
+                            candidate.describe()
+                        )
+                    })
+                    .collect();
+                let picked = TuiSelector::select_many(&items)?;
+                let worktrees: Vec<&WorktreeInfo> = stale
+                    .iter()
+                    .zip(&items)
+                    .filter(|(_, item)| picked.contains(item))
+                    .map(|(candidate, _)| &candidate.worktree)
+                    .collect();
+                if worktrees.is_empty() {
+                    info!("Nothing selected");
+                    return Ok(());
+                }
+                remove_selected(path_arg, &worktrees, &options, true)?;
+            }
+        }
+        Some(cli::Commands::Merge {
+            strategy,
+            message,
+            dry_run,
+        }) => {
             let worktrees = get_worktrees(path_arg)?;
 
             if worktrees.is_empty() {
-                println!("No worktrees found");
+                info!("No worktrees found");
                 return Ok(());
             }
 
-            let worktree_names: Vec<String> = worktrees
-                .iter()
-                .map(|wt| {
-                    let status = if wt.is_dirty { " (dirty)" } else { "" };
-                    format!("{} -> {} ({}){}", wt.name, wt.path, wt.branch, status)
-                })
-                .collect();
+            let worktree_names: Vec<String> = worktrees.iter().map(worktree_option).collect();
 
-            println!("Select source worktree (to merge FROM):");
+            info!("Select source worktree (to merge FROM):");
             let source_selected = TuiSelector::select_worktree(&worktree_names)?;
             let source_name = if let Some(selected) = source_selected {
                 if let Some(name) = selected.split(" -> ").next() {
                     name.to_string()
                 } else {
-                    println!("No source worktree selected, exiting");
+                    info!("No source worktree selected, exiting");
                     return Ok(());
                 }
             } else {
-                println!("No source worktree selected, exiting");
+                info!("No source worktree selected, exiting");
                 return Ok(());
             };
 
-            println!("Select target worktree (to merge INTO):");
+            info!("Select target worktree (to merge INTO):");
             let target_selected = TuiSelector::select_worktree(&worktree_names)?;
             let target_name = if let Some(selected) = target_selected {
                 if let Some(name) = selected.split(" -> ").next() {
                     name.to_string()
                 } else {
-                    println!("No target worktree selected, exiting");
+                    info!("No target worktree selected, exiting");
                     return Ok(());
                 }
             } else {
-                println!("No target worktree selected, exiting");
+                info!("No target worktree selected, exiting");
                 return Ok(());
             };
 
-            merge_worktrees(path_arg, &source_name, &target_name)?;
+            let find = |name: &str| {
+                worktrees
+                    .iter()
+                    .find(|wt| wt.name == name)
+                    .ok_or_else(|| anyhow::anyhow!("Could not find worktree '{name}'"))
+            };
+            let (source, target) = (find(&source_name)?, find(&target_name)?);
+            if !*dry_run && merge_in_progress(&target.path)? {
+                info!("'{target_name}' is in the middle of a merge");
+                return resolve::resolve_conflicts(target);
+            }
+
+            let options = MergeOptions {
+                strategy: *strategy,
+                message: message.clone(),
+                dry_run: *dry_run,
+            };
+            let outcome = merge_worktrees(path_arg, &source_name, &target_name, &options)?;
+            if let MergeOutcome::Conflict(conflicts) = &outcome
+                && !*dry_run
+            {
+                resolve::offer_resolution(target, &source.branch, &options, conflicts)?;
+            }
+            if *dry_run {
+                status::print_merge_preview(&outcome);
+                match outcome {
+                    MergeOutcome::Diverged => {
+                        anyhow::bail!("'{target_name}' can't be fast-forwarded to '{source_name}'")
+                    }
+                    MergeOutcome::Conflict(_) => {
+                        anyhow::bail!("Merging '{source_name}' into '{target_name}' would conflict")
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Some(cli::Commands::Land {
+            worktree,
+            strategy,
+            delete_remote,
+            resume,
+            abort,
+            jobs,
+        }) => {
+            let jobs = jobs.map_or_else(default_jobs, |jobs| jobs.get());
+            if *abort {
+                return land::abort(path_arg);
+            }
+            let target_path = if *resume {
+                land::resume(path_arg, jobs)?
+            } else {
+                let worktrees = list_worktrees(path_arg)?;
+                let default_branch = default_branch_name(path_arg)?;
+                let candidates: Vec<&WorktreeInfo> = worktrees
+                    .iter()
+                    .filter(|wt| {
+                        wt.state.is_present() && Some(&wt.branch) != default_branch.as_ref()
+                    })
+                    .collect();
+                let selected = match worktree {
+                    Some(target) => Some(
+                        find_worktree_target(&worktrees, target)
+                            .ok_or_else(|| anyhow::anyhow!("No worktree matches '{target}'"))?,
+                    ),
+                    None => match current_worktree(&candidates) {
+                        Some(current) => Some(current),
+                        None => {
+                            let options: Vec<String> =
+                                candidates.iter().map(|wt| worktree_option(wt)).collect();
+                            TuiSelector::select_worktree(&options)?.and_then(|selected| {
+                                let name = selected.split(" -> ").next()?;
+                                candidates.iter().copied().find(|wt| wt.name == name)
+                            })
+                        }
+                    },
+                };
+                let Some(selected) = selected else {
+                    info!("No worktree selected, exiting");
+                    return Ok(());
+                };
+                let options = LandOptions {
+                    strategy: *strategy,
+                    delete_remote: *delete_remote,
+                    jobs,
+                };
+                land::land(path_arg, selected, &options)?
+            };
+            // Output the default branch's worktree for shell integration
+            println!("{target_path}");
+        }
+        Some(cli::Commands::Conflicts { default_branch }) => {
+            let worktrees = get_worktrees(path_arg)?;
+            let report = predict_conflicts(path_arg, &worktrees, *default_branch)?;
+            status::print_conflicts(&report);
+        }
+        Some(cli::Commands::Fetch { prune, jobs }) => {
+            let jobs = jobs.map_or_else(default_jobs, |jobs| jobs.get());
+            let reports = fetch_all_remotes(path_arg, jobs, *prune)?;
+            let gone = worktrees_with_gone_upstream(path_arg)?;
+            status::print_fetch_report(&reports, &gone);
+        }
+        Some(cli::Commands::Pull {
+            rebase,
+            autostash,
+            jobs,
+        }) => {
+            let options = PullOptions {
+                jobs: jobs.map_or_else(default_jobs, |jobs| jobs.get()),
+                rebase: *rebase,
+                autostash: *autostash,
+            };
+            let reports = pull_all_worktrees(path_arg, &options)?;
+            report_updates(&reports)?;
         }
-        Some(cli::Commands::Pull) => {
-            pull_all_worktrees(path_arg)?;
+        Some(cli::Commands::Sync {
+            merge,
+            autostash,
+            jobs,
+        }) => {
+            let options = SyncOptions {
+                jobs: jobs.map_or_else(default_jobs, |jobs| jobs.get()),
+                merge: *merge,
+                autostash: *autostash,
+            };
+            let reports = sync_all_worktrees(path_arg, &options)?;
+            report_updates(&reports)?;
         }
         Some(cli::Commands::Shell { shell }) => match shell.as_str() {
             "zsh" => {
@@ -346,13 +625,21 @@ fn main() -> Result<()> {
         },
         None => {
             // Default behavior: show worktrees and allow selection or creation
-            let worktrees = get_worktrees(path_arg)?;
+            let mut worktrees = list_worktrees(path_arg)?;
 
             if worktrees.is_empty() {
                 // Don't output anything when no worktrees found for shell integration
                 return Ok(());
             }
 
+            if !cli.config.dir_only {
+                // Only check worktrees whose cached marker is out of date, so
+                // the picker opens quickly
+                for (index, is_dirty) in cached_dirty_states(path_arg, &worktrees) {
+                    worktrees[index].is_dirty = is_dirty;
+                }
+            }
+
             // Get current directory to filter out the main repo if we're in it
             let current_dir = std::env::current_dir()
                 .unwrap_or_else(|_| Path::new(".").to_path_buf())
@@ -360,10 +647,10 @@ fn main() -> Result<()> {
                 .unwrap_or_else(|_| Path::new(".").to_path_buf());
             let current_dir_str = current_dir.to_string_lossy().to_string();
 
-            // Filter out the current directory from available worktrees
+            // Filter out the current directory and worktrees whose directory is gone
             let available_worktrees: Vec<_> = worktrees
                 .iter()
-                .filter(|wt| wt.path != current_dir_str)
+                .filter(|wt| wt.path != current_dir_str && wt.state.is_present())
                 .collect();
 
             if available_worktrees.is_empty() {
@@ -383,11 +670,7 @@ fn main() -> Result<()> {
 
             // Add existing worktrees (excluding current directory)
             for worktree in &available_worktrees {
-                let status = if worktree.is_dirty { " (dirty)" } else { "" };
-                options.push(format!(
-                    "{} -> {} ({}){}",
-                    worktree.name, worktree.path, worktree.branch, status
-                ));
+                options.push(worktree_option(worktree));
             }
 
             // Add option to create new worktree
@@ -404,41 +687,32 @@ fn main() -> Result<()> {
                     all_branches.push("Create new branch".to_string());
 
                     if let Some(branch_selected) = TuiSelector::select_branch(&all_branches)? {
-                        let branch_name = if branch_selected == "Create new branch" {
+                        let (branch_name, track) = if branch_selected == "Create new branch" {
                             if let Some(new_branch) = TuiSelector::create_new_branch()? {
-                                new_branch
+                                (new_branch, None)
                             } else {
                                 return Ok(());
                             }
                         } else {
-                            // Extract branch name from selection
-                            branch_selected
-                                .split(": ")
-                                .nth(1)
-                                .unwrap_or(&branch_selected)
-                                .to_string()
+                            branch_from_selection(path_arg, &branch_selected)?
                         };
 
-                        let worktree_name = create_worktree(path_arg, &branch_name)?;
+                        let options = CreateOptions {
+                            track,
+                            ..Default::default()
+                        };
+                        let worktree_path = create_worktree(path_arg, &branch_name, &options)?;
 
-                        // Get the worktree path and output it for shell integration
-                        let updated_worktrees = get_worktrees(path_arg)?;
-                        if let Some(worktree) =
-                            updated_worktrees.iter().find(|wt| wt.name == worktree_name)
-                        {
-                            println!("{}", worktree.path);
-                        }
+                        // Output the worktree path for shell integration
+                        println!("{worktree_path}");
                     }
-                } else {
+                } else if let Some(worktree_name) = selected.split(" -> ").next()
+                    && let Some(worktree) = available_worktrees
+                        .iter()
+                        .find(|wt| wt.name == worktree_name)
+                {
                     // Handle existing worktree selection
-                    if let Some(worktree_name) = selected.split(" -> ").next() {
-                        if let Some(worktree) = available_worktrees
-                            .iter()
-                            .find(|wt| wt.name == worktree_name)
-                        {
-                            println!("{}", worktree.path);
-                        }
-                    }
+                    println!("{}", worktree.path);
                 }
             }
         }
@@ -447,6 +721,154 @@ fn main() -> Result<()> {
     Ok(())
 }
 
+struct RemoveOptions {
+    force: bool,
+    yes: bool,
+    unlock: bool,
+    delete_branch: bool,
+    delete_remote: bool,
+}
+
+/// Remove `worktrees` after checking nothing would be lost.
+///
+/// Locked worktrees need `--unlock`. Uncommitted changes and unpushed commits
+/// need `--force`, except when the worktree was `picked` in the selector,
+/// where a confirmation is enough. So does deleting a branch that isn't
+/// merged. If any worktree is refused, none are removed.
+fn remove_selected(
+    repo_path: &str,
+    worktrees: &[&WorktreeInfo],
+    options: &RemoveOptions,
+    picked: bool,
+) -> Result<()> {
+    let mut refusals = Vec::new();
+    let mut risky = false;
+    for worktree in worktrees {
+        if is_main_worktree(repo_path, worktree) {
+            refusals.push(format!("'{}' is the main worktree", worktree.name));
+            continue;
+        }
+        if let WorktreeState::Locked(reason) = &worktree.state
+            && !options.unlock
+        {
+            let reason = reason
+                .as_ref()
+                .map(|reason| format!(": {reason}"))
+                .unwrap_or_default();
+            refusals.push(format!(
+                "'{}' is locked{reason} (use --unlock)",
+                worktree.name
+            ));
+        }
+
+        // The listing may come from the dirty cache, so check again right
+        // before deleting anything
+        let risks = get_removal_risks(worktree)?;
+        let mut lost = Vec::new();
+        if risks.is_dirty {
+            lost.push("uncommitted changes".to_string());
+        }
+        if risks.unpushed > 0 {
+            let plural = if risks.unpushed == 1 { "" } else { "s" };
+            lost.push(format!("{} unpushed commit{plural}", risks.unpushed));
+        }
+        let branch = branch_to_delete(worktree, options);
+        if let Some(branch) = branch
+            && !is_branch_merged(repo_path, branch, options.delete_remote)?
+        {
+            lost.push(format!("unmerged branch '{branch}'"));
+        }
+        if !lost.is_empty() {
+            risky = true;
+            if !options.force && !picked {
+                refusals.push(format!(
+                    "'{}' has {} (use --force)",
+                    worktree.name,
+                    lost.join(" and ")
+                ));
+            }
+        }
+    }
+    ensure!(
+        refusals.is_empty(),
+        "Not removing anything:\n  {}",
+        refusals.join("\n  ")
+    );
+
+    // Picking a clean worktree in the selector is confirmation enough
+    if !options.yes && (!picked || (risky && !options.force)) {
+        let names: Vec<&str> = worktrees.iter().map(|wt| wt.name.as_str()).collect();
+        if !TuiSelector::confirm_deletion(&names.join(", "))? {
+            info!("Deletion cancelled");
+            return Ok(());
+        }
+    }
+
+    for worktree in worktrees {
+        remove_worktree(repo_path, &worktree.name, options.unlock)?;
+        if let Some(branch) = branch_to_delete(worktree, options) {
+            delete_branch(repo_path, branch, options.delete_remote)?;
+        }
+    }
+    Ok(())
+}
+
+/// Print the summary of `trees pull` or `trees sync` on stdout, failing if
+/// any worktree failed or conflicted
+fn report_updates(reports: &[UpdateReport]) -> Result<()> {
+    status::print_update_summary(reports);
+    let failed = reports.iter().filter(|report| report.failed()).count();
+    ensure!(
+        failed == 0,
+        "{failed} worktree(s) failed to update or conflicted"
+    );
+    Ok(())
+}
+
+/// The branch to delete along with `worktree`, if any
+fn branch_to_delete<'a>(worktree: &'a WorktreeInfo, options: &RemoveOptions) -> Option<&'a str> {
+    let branch = worktree.branch.as_str();
+    (options.delete_branch && branch != "detached" && branch != "unknown").then_some(branch)
+}
+
+/// The worktree in `worktrees` containing the current directory, if any
+fn current_worktree<'a>(worktrees: &[&'a WorktreeInfo]) -> Option<&'a WorktreeInfo> {
+    let current_dir = std::env::current_dir().ok()?.canonicalize().ok()?;
+    worktrees
+        .iter()
+        .copied()
+        .filter(|wt| current_dir.starts_with(&wt.path))
+        .max_by_key(|wt| wt.path.len())
+}
+
+/// Format a worktree as a selector option: `name -> path (branch)` plus any
+/// dirty or non-active state
+fn worktree_option(worktree: &WorktreeInfo) -> String {
+    let mut status = String::new();
+    if worktree.is_dirty {
+        status.push_str(" (dirty)");
+    }
+    if worktree.state != WorktreeState::Active {
+        status.push_str(&format!(" ({})", worktree.state.as_str()));
+    }
+    format!(
+        "{} -> {} ({}){}",
+        worktree.name, worktree.path, worktree.branch, status
+    )
+}
+
+/// Extract the branch name from a `local: ` or `remote: ` selection. Remote
+/// selections also return the remote branch the new local branch should track.
+fn branch_from_selection(repo_path: &str, selected: &str) -> Result<(String, Option<String>)> {
+    if let Some(remote_branch) = selected.strip_prefix("remote: ") {
+        let branch_name = local_branch_for_remote(repo_path, remote_branch)?;
+        Ok((branch_name, Some(remote_branch.to_string())))
+    } else {
+        let branch_name = selected.strip_prefix("local: ").unwrap_or(selected);
+        Ok((branch_name.to_string(), None))
+    }
+}
+
 #[test]
 fn verify_cli() {
     use clap::CommandFactory;
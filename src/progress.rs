@@ -0,0 +1,113 @@
+//! Live status lines for work that runs in parallel.
+//!
+//! On a terminal each task gets its own line on stderr, redrawn in place as
+//! its status changes. When stderr is not a terminal, or with `--quiet`,
+//! nothing is drawn and callers report results themselves.
+
+use std::io::{self, IsTerminal, Write};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::report::{self, Verbosity};
+
+/// Minimum time between redraws, so busy transfers don't flood the terminal
+const REDRAW_INTERVAL: Duration = Duration::from_millis(80);
+
+pub struct Progress {
+    live: bool,
+    width: usize,
+    state: Mutex<State>,
+}
+
+struct State {
+    labels: Vec<String>,
+    statuses: Vec<String>,
+    drawn: bool,
+    last_draw: Option<Instant>,
+}
+
+impl Progress {
+    /// One line per label, each starting out as "waiting"
+    pub fn new(labels: Vec<String>) -> Self {
+        let live =
+            !labels.is_empty() && io::stderr().is_terminal() && report::enabled(Verbosity::Normal);
+        Progress {
+            live,
+            width: labels.iter().map(|label| label.len()).max().unwrap_or(0),
+            state: Mutex::new(State {
+                statuses: vec!["waiting".to_string(); labels.len()],
+                labels,
+                drawn: false,
+                last_draw: None,
+            }),
+        }
+    }
+
+    /// Whether progress is being drawn
+    pub fn is_live(&self) -> bool {
+        self.live
+    }
+
+    /// Update the status of task `index`, redrawing if enough time has passed
+    pub fn set(&self, index: usize, status: impl Into<String>) {
+        let mut state = self.state.lock().unwrap();
+        state.statuses[index] = status.into();
+        if state
+            .last_draw
+            .is_none_or(|last| last.elapsed() >= REDRAW_INTERVAL)
+        {
+            self.draw(&mut state);
+        }
+    }
+
+    /// Set the final status of task `index` and redraw right away
+    pub fn finish(&self, index: usize, status: impl Into<String>) {
+        let mut state = self.state.lock().unwrap();
+        state.statuses[index] = status.into();
+        self.draw(&mut state);
+    }
+
+    fn draw(&self, state: &mut State) {
+        if !self.live {
+            return;
+        }
+        let mut stderr = io::stderr().lock();
+        if state.drawn {
+            // Move back up to the first line of the previous drawing
+            let _ = write!(stderr, "\x1b[{}A", state.labels.len());
+        }
+        for (label, status) in state.labels.iter().zip(&state.statuses) {
+            let _ = writeln!(
+                stderr,
+                "\x1b[2K{label:<width$}  {status}",
+                width = self.width
+            );
+        }
+        let _ = stderr.flush();
+        state.drawn = true;
+        state.last_draw = Some(Instant::now());
+    }
+}
+
+/// Describe a fetch's transfer progress, e.g. "42/100 objects, 1.2 MiB"
+pub fn describe_transfer(stats: &git2::Progress) -> String {
+    let bytes = stats.received_bytes() as f64;
+    let size = if bytes >= 1024.0 * 1024.0 {
+        format!("{:.1} MiB", bytes / (1024.0 * 1024.0))
+    } else {
+        format!("{:.1} KiB", bytes / 1024.0)
+    };
+    if stats.received_objects() < stats.total_objects() {
+        format!(
+            "{}/{} objects, {size}",
+            stats.received_objects(),
+            stats.total_objects()
+        )
+    } else {
+        format!(
+            "resolving deltas {}/{}, {size}",
+            stats.indexed_deltas(),
+            stats.total_deltas()
+        )
+    }
+}
@@ -0,0 +1,51 @@
+//! Diagnostics for the person running trees.
+//!
+//! Data a command was asked for (paths, listings, shell scripts) is printed to
+//! stdout with `println!`. Everything else goes through the macros here to
+//! stderr, so `DIR=$(trees-bin ...)` only ever captures data.
+
+use std::sync::atomic::{AtomicU8, Ordering};
+
+#[derive(Clone, Copy)]
+pub enum Verbosity {
+    Quiet = 0,
+    Normal = 1,
+    Verbose = 2,
+}
+
+static VERBOSITY: AtomicU8 = AtomicU8::new(Verbosity::Normal as u8);
+
+pub fn set_verbosity(verbosity: Verbosity) {
+    VERBOSITY.store(verbosity as u8, Ordering::Relaxed);
+}
+
+pub fn enabled(verbosity: Verbosity) -> bool {
+    VERBOSITY.load(Ordering::Relaxed) >= verbosity as u8
+}
+
+/// Progress and status messages, hidden by `--quiet`
+macro_rules! info {
+    ($($arg:tt)*) => {
+        if $crate::report::enabled($crate::report::Verbosity::Normal) {
+            eprintln!($($arg)*);
+        }
+    };
+}
+
+/// Extra detail, only shown with `--verbose`
+macro_rules! verbose {
+    ($($arg:tt)*) => {
+        if $crate::report::enabled($crate::report::Verbosity::Verbose) {
+            eprintln!($($arg)*);
+        }
+    };
+}
+
+/// Problems that don't stop the command, shown even with `--quiet`
+macro_rules! warning {
+    ($($arg:tt)*) => {
+        eprintln!("warning: {}", format_args!($($arg)*));
+    };
+}
+
+pub(crate) use {info, verbose, warning};
@@ -0,0 +1,154 @@
+//! Guided resolution of merge conflicts.
+//!
+//! `trees merge` works merges out in memory, so a conflict changes nothing
+//! until the user chooses to resolve it. Only then is the conflicted merge
+//! written into the target worktree, and its files are resolved one at a time
+//! in the selector: in `$EDITOR`, with `git mergetool`, or by taking either
+//! side. The merge is then committed or aborted. A worktree left in the
+//! middle of a merge is picked up again by the next `trees merge`.
+
+use anyhow::{Context, Result};
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use crate::git::{
+    MergeOptions, WorktreeInfo, abort_merge, conflicted_files, continue_merge, mark_resolved,
+    start_conflicted_merge, take_side,
+};
+use crate::report::{info, warning};
+use crate::tui::TuiSelector;
+
+const RESOLVE: &str = "Resolve the conflicts";
+const ABORT: &str = "Abort the merge";
+const CONTINUE: &str = "Continue (commit the merge)";
+const EDIT: &str = "Open in $EDITOR";
+const MERGETOOL: &str = "Open in git mergetool";
+const OURS: &str = "Take ours (the target's version)";
+const THEIRS: &str = "Take theirs (the source's version)";
+
+/// Offer to resolve the conflicts of merging `source_branch` into `target`.
+/// Declining leaves `target` untouched and fails.
+pub fn offer_resolution(
+    target: &WorktreeInfo,
+    source_branch: &str,
+    options: &MergeOptions,
+    conflicts: &[String],
+) -> Result<()> {
+    info!(
+        "Merging '{source_branch}' into '{}' conflicts in:",
+        target.name
+    );
+    for path in conflicts {
+        info!("  {path}");
+    }
+    let choices = vec![RESOLVE.to_string(), ABORT.to_string()];
+    if TuiSelector::select_option(&choices)?.as_deref() != Some(RESOLVE) {
+        anyhow::bail!(
+            "Merging '{source_branch}' into '{}' conflicts in {}; nothing was changed",
+            target.name,
+            conflicts.join(", ")
+        );
+    }
+    start_conflicted_merge(&target.path, source_branch, options)?;
+    resolve_conflicts(target)
+}
+
+/// Resolve the merge in progress in `worktree` file by file, then commit or
+/// abort it. Leaving the selector keeps the merge in progress.
+pub fn resolve_conflicts(worktree: &WorktreeInfo) -> Result<()> {
+    loop {
+        let conflicts = conflicted_files(&worktree.path)?;
+        if conflicts.is_empty() {
+            info!("All conflicts in '{}' are resolved", worktree.name);
+        } else {
+            info!("Pick a file to resolve in '{}':", worktree.name);
+        }
+        let mut choices = conflicts.clone();
+        if conflicts.is_empty() {
+            choices.push(CONTINUE.to_string());
+        }
+        choices.push(ABORT.to_string());
+
+        let Some(choice) = TuiSelector::select_option(&choices)? else {
+            info!(
+                "'{}' is still in the middle of a merge; run `trees merge` again to finish it",
+                worktree.name
+            );
+            return Ok(());
+        };
+        // Match by position so a file can't be mistaken for an action
+        let position = choices.iter().position(|item| *item == choice);
+        match position {
+            Some(index) if index < conflicts.len() => resolve_file(worktree, &conflicts[index])?,
+            _ if choice == CONTINUE => {
+                continue_merge(&worktree.path)?;
+                info!("Merge committed in '{}'", worktree.name);
+                return Ok(());
+            }
+            _ => {
+                abort_merge(&worktree.path)?;
+                info!("Merge aborted, '{}' is back where it was", worktree.name);
+                return Ok(());
+            }
+        }
+    }
+}
+
+fn resolve_file(worktree: &WorktreeInfo, file: &str) -> Result<()> {
+    let actions = [EDIT, MERGETOOL, OURS, THEIRS].map(str::to_string);
+    let Some(action) = TuiSelector::select_option(&actions)? else {
+        return Ok(());
+    };
+    let full_path = Path::new(&worktree.path).join(file);
+    match action.as_str() {
+        EDIT => {
+            run_editor(&full_path)?;
+            if has_conflict_markers(&full_path) {
+                warning!("'{file}' still has conflict markers");
+            } else {
+                mark_resolved(&worktree.path, file)?;
+            }
+        }
+        MERGETOOL => {
+            // git mergetool stages the file itself once it is resolved
+            let status = Command::new("git")
+                .args(["mergetool", "--", file])
+                .current_dir(&worktree.path)
+                .status()
+                .context("Failed to run git mergetool")?;
+            if !status.success() {
+                warning!("git mergetool did not resolve '{file}'");
+            }
+        }
+        OURS => take_side(&worktree.path, file, false)?,
+        _ => take_side(&worktree.path, file, true)?,
+    }
+    Ok(())
+}
+
+/// Open `path` in `$VISUAL` or `$EDITOR`, falling back to `vi`
+fn run_editor(path: &Path) -> Result<()> {
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+    // Through the shell, so editors with arguments like "code --wait" work
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(format!("{editor} \"$@\""))
+        .arg(&editor)
+        .arg(path)
+        .status()
+        .with_context(|| format!("Failed to run {editor}"))?;
+    anyhow::ensure!(status.success(), "{editor} exited with {status}");
+    Ok(())
+}
+
+fn has_conflict_markers(path: &Path) -> bool {
+    fs::read(path).is_ok_and(|contents| {
+        String::from_utf8_lossy(&contents)
+            .lines()
+            .any(|line| line.starts_with("<<<<<<< ") || line.starts_with(">>>>>>> "))
+    })
+}
@@ -0,0 +1,283 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::git::{
+    BranchChange, ConflictReport, FetchReport, MergeOutcome, StaleWorktree, UpdateReport,
+    UpdateResult, WorktreeInfo, get_worktree_status,
+};
+use crate::report::{info, warning};
+
+const HEADERS: [&str; 12] = [
+    "NAME",
+    "BRANCH",
+    "HEAD",
+    "UPSTREAM",
+    "AHEAD",
+    "BEHIND",
+    "STAGED",
+    "UNSTAGED",
+    "UNTRACKED",
+    "STASH",
+    "IN PROGRESS",
+    "LAST COMMIT",
+];
+
+/// Print one row per worktree with its branch, upstream, changes and activity
+pub fn print_status(worktrees: &[WorktreeInfo]) {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0);
+
+    let mut rows = vec![HEADERS.map(str::to_string).to_vec()];
+    for worktree in worktrees {
+        rows.push(status_row(worktree, now));
+    }
+    print_table(&rows);
+}
+
+fn status_row(worktree: &WorktreeInfo, now: i64) -> Vec<String> {
+    let dash = || "-".to_string();
+    let mut row = vec![worktree.name.clone(), worktree.branch.clone()];
+
+    if !worktree.state.is_present() {
+        row.extend((0..9).map(|_| dash()));
+        row.push(format!("({})", worktree.state.as_str()));
+        return row;
+    }
+    let status = match get_worktree_status(worktree) {
+        Ok(status) => status,
+        Err(e) => {
+            warning!("Failed to get status of '{}': {e}", worktree.name);
+            row.extend((0..10).map(|_| dash()));
+            return row;
+        }
+    };
+
+    let details = &status.details;
+    row.push(
+        details
+            .head
+            .as_deref()
+            .map(|oid| oid[..8].to_string())
+            .unwrap_or_else(dash),
+    );
+    row.push(details.upstream.clone().unwrap_or_else(dash));
+    match details.ahead_behind {
+        Some((ahead, behind)) => {
+            row.push(ahead.to_string());
+            row.push(behind.to_string());
+        }
+        None => row.extend([dash(), dash()]),
+    }
+    row.push(status.changes.staged.to_string());
+    row.push(status.changes.unstaged.to_string());
+    row.push(status.changes.untracked.to_string());
+    row.push(status.stashes.to_string());
+    row.push(status.operation.map(str::to_string).unwrap_or_else(dash));
+    row.push(
+        status
+            .last_commit
+            .map(|time| format_age(now - time))
+            .unwrap_or_else(dash),
+    );
+    row
+}
+
+/// Summarize `trees pull` or `trees sync` with one row per worktree: its
+/// name, its branch and what happened to it
+pub fn print_update_summary(reports: &[UpdateReport]) {
+    let mut rows = vec![vec![
+        "NAME".to_string(),
+        "BRANCH".to_string(),
+        "RESULT".to_string(),
+    ]];
+    for report in reports {
+        let result = report
+            .result
+            .as_ref()
+            .map_or("failed", UpdateResult::as_str);
+        rows.push(vec![
+            report.name.clone(),
+            report.branch.clone(),
+            result.to_string(),
+        ]);
+    }
+    print_table(&rows);
+}
+
+/// Print what changed on each remote, one branch per line (`new`, `deleted`
+/// or `forced` plus the branch), and `gone` for worktrees whose upstream no
+/// longer exists
+pub fn print_fetch_report(reports: &[FetchReport], gone: &[(WorktreeInfo, String)]) {
+    let mut changed = false;
+    for (branch, change) in reports.iter().flat_map(|report| &report.changes) {
+        changed = true;
+        match change {
+            BranchChange::New => println!("new     {branch}"),
+            BranchChange::Deleted => println!("deleted {branch}"),
+            BranchChange::ForceUpdated { old, new } => {
+                let short = |oid: &git2::Oid| oid.to_string()[..8].to_string();
+                println!("forced  {branch} {}...{}", short(old), short(new));
+            }
+        }
+    }
+    for (worktree, upstream) in gone {
+        changed = true;
+        println!("gone    {upstream} (worktree '{}')", worktree.name);
+    }
+    if !changed {
+        info!("No remote branches changed");
+    }
+}
+
+/// List the worktrees `trees clean` would offer, with why each is stale
+pub fn print_stale_worktrees(stale: &[StaleWorktree]) {
+    let mut rows = vec![vec![
+        "NAME".to_string(),
+        "BRANCH".to_string(),
+        "REASON".to_string(),
+    ]];
+    for candidate in stale {
+        rows.push(vec![
+            candidate.worktree.name.clone(),
+            candidate.worktree.branch.clone(),
+            candidate.describe(),
+        ]);
+    }
+    print_table(&rows);
+}
+
+/// Print what `trees merge --dry-run` found: `up-to-date`, `fast-forward`,
+/// `clean` or `diverged`, or a `conflict` line per conflicting path
+pub fn print_merge_preview(outcome: &MergeOutcome) {
+    match outcome {
+        MergeOutcome::UpToDate => println!("up-to-date"),
+        MergeOutcome::FastForward => println!("fast-forward"),
+        MergeOutcome::Clean => println!("clean"),
+        MergeOutcome::Diverged => println!("diverged"),
+        MergeOutcome::Conflict(paths) => {
+            for path in paths {
+                println!("conflict {path}");
+            }
+        }
+    }
+}
+
+/// Print `trees conflicts` as a matrix of `clean` and `conflict` cells, one
+/// row per worktree, followed by the conflicting paths of each pair
+pub fn print_conflicts(report: &ConflictReport) {
+    let cell = |i: usize, j: usize| {
+        let outcome = report
+            .results
+            .get(&(i, j))
+            .or_else(|| report.results.get(&(j, i)));
+        match outcome {
+            Some(MergeOutcome::Conflict(paths)) => format!("conflict ({})", paths.len()),
+            Some(_) => "clean".to_string(),
+            None => "-".to_string(),
+        }
+    };
+
+    let columns: Vec<usize> = match report.default_branch {
+        Some(_) => vec![report.names.len() - 1],
+        None => (0..report.names.len()).collect(),
+    };
+    let rows_count = match report.default_branch {
+        Some(_) => report.names.len() - 1,
+        None => report.names.len(),
+    };
+    if rows_count == 0 {
+        info!("No worktrees to compare");
+        return;
+    }
+    let mut header = vec![String::new()];
+    header.extend(columns.iter().map(|&j| report.names[j].clone()));
+    let mut rows = vec![header];
+    for i in 0..rows_count {
+        let mut row = vec![report.names[i].clone()];
+        row.extend(columns.iter().map(|&j| cell(i, j)));
+        rows.push(row);
+    }
+    print_table(&rows);
+
+    let conflicts: Vec<_> = report
+        .results
+        .iter()
+        .filter_map(|(&(i, j), outcome)| match outcome {
+            MergeOutcome::Conflict(paths) => Some((i, j, paths)),
+            _ => None,
+        })
+        .collect();
+    if !conflicts.is_empty() {
+        println!();
+    }
+    for (i, j, paths) in conflicts {
+        println!(
+            "{} <-> {}: {}",
+            report.names[i],
+            report.names[j],
+            paths.join(", ")
+        );
+    }
+}
+
+fn print_table(rows: &[Vec<String>]) {
+    for line in table_lines(rows) {
+        println!("{line}");
+    }
+}
+
+/// Left-align the cells of `rows` into columns two spaces apart
+fn table_lines(rows: &[Vec<String>]) -> Vec<String> {
+    let mut widths = vec![0; rows.first().map_or(0, Vec::len)];
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    rows.iter()
+        .map(|row| {
+            let line: Vec<String> = row
+                .iter()
+                .zip(&widths)
+                .map(|(cell, width)| format!("{cell:<width$}"))
+                .collect();
+            line.join("  ").trim_end().to_string()
+        })
+        .collect()
+}
+
+/// Describe an age in seconds the way git's relative dates do, e.g. "3 days ago"
+fn format_age(seconds: i64) -> String {
+    const UNITS: [(&str, i64); 6] = [
+        ("year", 365 * 24 * 60 * 60),
+        ("month", 30 * 24 * 60 * 60),
+        ("week", 7 * 24 * 60 * 60),
+        ("day", 24 * 60 * 60),
+        ("hour", 60 * 60),
+        ("minute", 60),
+    ];
+    for (unit, size) in UNITS {
+        let count = seconds / size;
+        if count > 0 {
+            let plural = if count == 1 { "" } else { "s" };
+            return format!("{count} {unit}{plural} ago");
+        }
+    }
+    "just now".to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_age() {
+        assert_eq!(format_age(-5), "just now");
+        assert_eq!(format_age(59), "just now");
+        assert_eq!(format_age(60), "1 minute ago");
+        assert_eq!(format_age(2 * 60 * 60 + 5), "2 hours ago");
+        assert_eq!(format_age(3 * 24 * 60 * 60), "3 days ago");
+        assert_eq!(format_age(400 * 24 * 60 * 60), "1 year ago");
+    }
+}
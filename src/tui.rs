@@ -3,6 +3,8 @@ use ff::{TuiConfig, run_tui_with_config};
 use std::env;
 use std::io::{self, Write};
 
+use crate::report::info;
+
 pub struct TuiSelector;
 
 impl TuiSelector {
@@ -15,7 +17,7 @@ impl TuiSelector {
 
     pub fn select_worktree(worktrees: &[String]) -> Result<Option<String>> {
         if worktrees.is_empty() {
-            println!("No worktrees found");
+            info!("No worktrees found");
             return Ok(None);
         }
 
@@ -47,7 +49,7 @@ impl TuiSelector {
 
     pub fn select_branch(branches: &[String]) -> Result<Option<String>> {
         if branches.is_empty() {
-            println!("No branches found");
+            info!("No branches found");
             return Ok(None);
         }
 
@@ -77,9 +79,45 @@ impl TuiSelector {
         }
     }
 
+    /// Pick one of `items`
+    pub fn select_option(items: &[String]) -> Result<Option<String>> {
+        if env::var("TERM").is_err() || env::var("TERM").unwrap() == "dumb" {
+            return Self::fallback_selection(items);
+        }
+
+        let height = Self::calculate_height(items.len());
+        let mut config = TuiConfig::with_height(height);
+        config.show_help_text = false;
+
+        match run_tui_with_config(items.to_vec(), false, config) {
+            Ok(selected) => Ok(selected.into_iter().next()),
+            Err(_) => Self::fallback_selection(items),
+        }
+    }
+
+    /// Pick any number of `items`; an empty result means nothing was picked
+    pub fn select_many(items: &[String]) -> Result<Vec<String>> {
+        if items.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        if env::var("TERM").is_err() || env::var("TERM").unwrap() == "dumb" {
+            return Self::fallback_multi_selection(items);
+        }
+
+        let height = Self::calculate_height(items.len());
+        let mut config = TuiConfig::with_height(height);
+        config.show_help_text = false;
+
+        match run_tui_with_config(items.to_vec(), true, config) {
+            Ok(selected) => Ok(selected),
+            Err(_) => Self::fallback_multi_selection(items),
+        }
+    }
+
     pub fn create_new_branch() -> Result<Option<String>> {
-        print!("Enter new branch name: ");
-        io::stdout().flush()?;
+        eprint!("Enter new branch name: ");
+        io::stderr().flush()?;
 
         let mut input = String::new();
         io::stdin().read_line(&mut input)?;
@@ -117,12 +155,12 @@ impl TuiSelector {
     }
 
     fn fallback_selection(items: &[String]) -> Result<Option<String>> {
-        println!("Select an option:");
+        eprintln!("Select an option:");
         for (i, item) in items.iter().enumerate() {
-            println!("{}. {}", i + 1, item);
+            eprintln!("{}. {}", i + 1, item);
         }
-        print!("Enter number (1-{}): ", items.len());
-        io::stdout().flush()?;
+        eprint!("Enter number (1-{}): ", items.len());
+        io::stderr().flush()?;
 
         let mut input = String::new();
         io::stdin().read_line(&mut input)?;
@@ -135,9 +173,39 @@ impl TuiSelector {
         }
     }
 
+    fn fallback_multi_selection(items: &[String]) -> Result<Vec<String>> {
+        eprintln!("Select options:");
+        for (i, item) in items.iter().enumerate() {
+            eprintln!("{}. {}", i + 1, item);
+        }
+        eprint!(
+            "Enter numbers separated by spaces (1-{}) or 'all': ",
+            items.len()
+        );
+        io::stderr().flush()?;
+
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+
+        if input.trim() == "all" {
+            return Ok(items.to_vec());
+        }
+        let mut selected = Vec::new();
+        for choice in input.split_whitespace() {
+            if let Ok(choice) = choice.parse::<usize>()
+                && choice > 0
+                && choice <= items.len()
+                && !selected.contains(&items[choice - 1])
+            {
+                selected.push(items[choice - 1].clone());
+            }
+        }
+        Ok(selected)
+    }
+
     fn fallback_confirmation(worktree_name: &str) -> Result<bool> {
-        print!("Are you sure you want to delete {}? (y/N): ", worktree_name);
-        io::stdout().flush()?;
+        eprint!("Are you sure you want to delete {}? (y/N): ", worktree_name);
+        io::stderr().flush()?;
 
         let mut input = String::new();
         io::stdin().read_line(&mut input)?;
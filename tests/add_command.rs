@@ -0,0 +1,154 @@
+use tempfile::tempdir;
+
+mod common;
+use common::{commit_file, git, init_repo, stdout_ok, trees};
+
+fn trees_add(repo: &std::path::Path, args: &[&str]) -> String {
+    let output = stdout_ok(trees(repo, &["add"]).args(args).arg("--dir-only"));
+    output.lines().last().unwrap_or_default().to_string()
+}
+
+#[test]
+fn test_add_branch_without_selector() {
+    let dir = tempdir().unwrap();
+    let repo = init_repo(dir.path(), "repo");
+
+    let path = trees_add(&repo, &["feat/one"]);
+
+    let expected = dir.path().join("repo-feat-one").canonicalize().unwrap();
+    assert_eq!(path, expected.to_string_lossy());
+    assert_eq!(git(&expected, &["branch", "--show-current"]), "feat/one");
+    // No stray branch named after the worktree should be created
+    assert!(!git(&repo, &["branch", "--list"]).contains("repo-feat-one"));
+}
+
+#[test]
+fn test_add_with_base_and_dir() {
+    let dir = tempdir().unwrap();
+    let repo = init_repo(dir.path(), "repo");
+    let base = git(&repo, &["rev-parse", "HEAD"]);
+    commit_file(&repo, "second.txt", "second\n", "Second commit");
+
+    let target = dir.path().join("elsewhere").join("wt");
+    std::fs::create_dir_all(target.parent().unwrap()).unwrap();
+    let path = trees_add(
+        &repo,
+        &["topic", "--base", &base, "--dir", target.to_str().unwrap()],
+    );
+
+    assert_eq!(path, target.canonicalize().unwrap().to_string_lossy());
+    assert_eq!(git(&target, &["rev-parse", "HEAD"]), base);
+}
+
+#[test]
+fn test_add_with_track_sets_upstream() {
+    let dir = tempdir().unwrap();
+    let upstream = init_repo(dir.path(), "upstream");
+    git(&upstream, &["branch", "shared"]);
+    git(
+        dir.path(),
+        &["clone", "-q", upstream.to_str().unwrap(), "clone"],
+    );
+    let repo = dir.path().join("clone");
+
+    let path = trees_add(&repo, &["mine", "--track", "origin/shared"]);
+
+    let worktree = std::path::Path::new(&path);
+    assert_eq!(
+        git(worktree, &["rev-parse", "--abbrev-ref", "mine@{upstream}"]),
+        "origin/shared"
+    );
+}
+
+#[test]
+fn test_add_base_requires_branch() {
+    let dir = tempdir().unwrap();
+    let repo = init_repo(dir.path(), "repo");
+
+    trees(&repo, &["add", "--base", "HEAD"]).assert().failure();
+}
+
+/// Pick `item` from the fallback (non-TTY) branch selector of `trees add`
+fn trees_add_select(repo: &std::path::Path, item: &str) -> std::process::Output {
+    let run = |input: &str| {
+        trees(repo, &["add", "--dir-only"])
+            .env("TERM", "dumb")
+            .write_stdin(input.to_string())
+            .output()
+            .unwrap()
+    };
+    // An invalid choice lists the options without selecting anything
+    let menu = String::from_utf8_lossy(&run("0\n").stderr).to_string();
+    let number = menu
+        .lines()
+        .find_map(|line| line.strip_suffix(&format!(". {item}")))
+        .unwrap_or_else(|| panic!("'{item}' not offered in:\n{menu}"))
+        .to_string();
+    run(&format!("{number}\n"))
+}
+
+#[test]
+fn test_add_remote_selection_creates_tracking_branch() {
+    let dir = tempdir().unwrap();
+    let upstream = init_repo(dir.path(), "upstream");
+    git(&upstream, &["checkout", "-q", "-b", "colleague"]);
+    commit_file(&upstream, "work.txt", "work\n", "Colleague work");
+    git(&upstream, &["checkout", "-q", "main"]);
+    let repo = dir.path().join("clone");
+    git(
+        dir.path(),
+        &["clone", "-q", upstream.to_str().unwrap(), "clone"],
+    );
+    // A second remote with a differently named branch
+    git(
+        &repo,
+        &["remote", "add", "fork", upstream.to_str().unwrap()],
+    );
+    git(&repo, &["fetch", "-q", "fork"]);
+
+    let output = trees_add_select(&repo, "remote: origin/colleague");
+    assert!(output.status.success());
+    let worktree = dir.path().join("clone-colleague");
+    assert_eq!(
+        git(&worktree, &["rev-parse", "HEAD"]),
+        git(&upstream, &["rev-parse", "colleague"])
+    );
+    assert_eq!(
+        git(&worktree, &["rev-parse", "--abbrev-ref", "@{upstream}"]),
+        "origin/colleague"
+    );
+
+    git(&upstream, &["branch", "other"]);
+    git(&repo, &["fetch", "-q", "fork"]);
+    let output = trees_add_select(&repo, "remote: fork/other");
+    assert!(output.status.success());
+    let worktree = dir.path().join("clone-other");
+    assert_eq!(
+        git(&worktree, &["rev-parse", "--abbrev-ref", "@{upstream}"]),
+        "fork/other"
+    );
+}
+
+#[test]
+fn test_add_remote_selection_uses_existing_local_branch() {
+    let dir = tempdir().unwrap();
+    let upstream = init_repo(dir.path(), "upstream");
+    git(&upstream, &["branch", "shared"]);
+    let repo = dir.path().join("clone");
+    git(
+        dir.path(),
+        &["clone", "-q", upstream.to_str().unwrap(), "clone"],
+    );
+    git(&repo, &["branch", "--track", "shared", "origin/shared"]);
+    git(&repo, &["checkout", "-q", "shared"]);
+    commit_file(&repo, "local.txt", "local\n", "Local work");
+    let local_head = git(&repo, &["rev-parse", "HEAD"]);
+    git(&repo, &["checkout", "-q", "main"]);
+
+    let output = trees_add_select(&repo, "remote: origin/shared");
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("already exists"));
+    let worktree = dir.path().join("clone-shared");
+    assert_eq!(git(&worktree, &["rev-parse", "HEAD"]), local_head);
+}
@@ -0,0 +1,145 @@
+use assert_cmd::Command;
+use std::path::{Path, PathBuf};
+use tempfile::tempdir;
+
+mod common;
+use common::{commit_file, git, init_repo, stdout_ok, trees};
+
+/// Run `trees add ... --dir-only` and return the printed worktree path
+fn trees_add(repo: &Path, branch: &str) -> String {
+    let output = stdout_ok(&mut trees(repo, &["add", branch, "--dir-only"]));
+    output.lines().last().unwrap_or_default().to_string()
+}
+
+/// Set up `project/.bare` with a `.git` pointer file, like `trees clone`
+fn bare_project(parent: &Path) -> PathBuf {
+    let upstream = init_repo(parent, "upstream");
+    let project = parent.join("project");
+    std::fs::create_dir_all(&project).unwrap();
+    git(
+        &project,
+        &["clone", "-q", "--bare", upstream.to_str().unwrap(), ".bare"],
+    );
+    std::fs::write(project.join(".git"), "gitdir: ./.bare\n").unwrap();
+    project
+}
+
+#[test]
+fn test_bare_project_add_list_and_jump() {
+    let dir = tempdir().unwrap();
+    let project = bare_project(dir.path());
+
+    let main = trees_add(&project, "main");
+    let feature = trees_add(&project, "feature");
+    let main_path = project.join("main").canonicalize().unwrap();
+    let feature_path = project.join("feature").canonicalize().unwrap();
+    assert_eq!(main, main_path.to_string_lossy());
+    assert_eq!(feature, feature_path.to_string_lossy());
+
+    let list = stdout_ok(&mut trees(&project, &["list"]));
+    assert_eq!(list.lines().count(), 2, "unexpected list output:\n{list}");
+    assert!(!list.contains(".bare"));
+    assert!(list.contains("[main]"));
+    assert!(list.contains("[feature]"));
+
+    // Jumping from one worktree never offers the bare repository
+    let output = Command::cargo_bin("trees-bin")
+        .unwrap()
+        .current_dir(&main_path)
+        .arg("--dir-only")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout).trim(),
+        feature_path.to_string_lossy()
+    );
+}
+
+#[test]
+fn test_bare_project_remove_worktree() {
+    let dir = tempdir().unwrap();
+    let project = bare_project(dir.path());
+    trees_add(&project, "main");
+    trees_add(&project, "feature");
+
+    let menu = trees(&project, &["rm"])
+        .env("TERM", "dumb")
+        .write_stdin("0\n")
+        .output()
+        .unwrap();
+    let menu = String::from_utf8_lossy(&menu.stderr).to_string();
+    let number = menu
+        .lines()
+        .find(|line| line.contains(". feature ->"))
+        .and_then(|line| line.split('.').next())
+        .unwrap_or_else(|| panic!("feature not offered in:\n{menu}"))
+        .to_string();
+
+    trees(&project, &["rm"])
+        .env("TERM", "dumb")
+        .write_stdin(format!("{number}\n"))
+        .assert()
+        .success();
+    assert!(!project.join("feature").exists());
+    assert!(project.join("main").exists());
+}
+
+#[test]
+fn test_plain_bare_repository_worktree_location() {
+    let dir = tempdir().unwrap();
+    let upstream = init_repo(dir.path(), "upstream");
+    git(
+        dir.path(),
+        &[
+            "clone",
+            "-q",
+            "--bare",
+            upstream.to_str().unwrap(),
+            "repo.git",
+        ],
+    );
+
+    let path = trees_add(&dir.path().join("repo.git"), "topic");
+    let expected = dir.path().join("repo-topic").canonicalize().unwrap();
+    assert_eq!(path, expected.to_string_lossy());
+}
+
+#[test]
+fn test_separate_git_dir() {
+    let dir = tempdir().unwrap();
+    let store = dir.path().join("store.git");
+    git(
+        dir.path(),
+        &[
+            "init",
+            "-q",
+            "-b",
+            "main",
+            "--separate-git-dir",
+            store.to_str().unwrap(),
+            "repo",
+        ],
+    );
+    let repo = dir.path().join("repo");
+    commit_file(&repo, "README.md", "# Test\n", "Initial commit");
+
+    let path = trees_add(&repo, "topic");
+    let expected = dir.path().join("repo-topic").canonicalize().unwrap();
+    assert_eq!(path, expected.to_string_lossy());
+
+    let list = stdout_ok(&mut trees(&repo, &["list"]));
+    let main_line = list.lines().next().unwrap();
+    assert!(main_line.starts_with(&format!("{} ", repo.canonicalize().unwrap().display())));
+    assert!(main_line.ends_with("[main]"));
+}
+
+#[test]
+fn test_git_dir_environment_variable() {
+    let dir = tempdir().unwrap();
+    let project = bare_project(dir.path());
+    trees_add(&project, "main");
+
+    let list = stdout_ok(trees(dir.path(), &["list"]).env("GIT_DIR", project.join(".bare")));
+    assert!(list.contains("[main]"), "unexpected list output:\n{list}");
+}
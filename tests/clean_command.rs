@@ -0,0 +1,154 @@
+use std::path::{Path, PathBuf};
+use tempfile::tempdir;
+
+mod common;
+use common::{add_worktree, commit_file, git, init_repo, run_ok, trees};
+
+const DAY: u64 = 24 * 60 * 60;
+
+fn clean_command(repo: &Path, args: &[&str], stdin: &str) -> assert_cmd::Command {
+    let mut command = trees(repo, &["clean"]);
+    command.env("TERM", "dumb").args(args).write_stdin(stdin);
+    command
+}
+
+/// Make the worktree `name` look untouched for `days` days: an old commit on
+/// its branch and old HEAD and index files
+fn make_idle(repo: &Path, worktree: &Path, name: &str, days: u64) {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let then = format!("@{}", now - days * DAY);
+    std::fs::write(worktree.join("old.txt"), "old\n").unwrap();
+    git(worktree, &["add", "old.txt"]);
+    let status = std::process::Command::new("git")
+        .args(["commit", "-q", "-m", "Old work", "--date", &then])
+        .current_dir(worktree)
+        .env("GIT_AUTHOR_NAME", "Test User")
+        .env("GIT_AUTHOR_EMAIL", "test@example.com")
+        .env("GIT_COMMITTER_NAME", "Test User")
+        .env("GIT_COMMITTER_EMAIL", "test@example.com")
+        .env("GIT_COMMITTER_DATE", &then)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    git(worktree, &["push", "-q", "-u", "origin", name]);
+    let git_dir = repo.join(".git/worktrees").join(name);
+    for file in ["HEAD", "index"] {
+        let status = std::process::Command::new("touch")
+            .args(["-d", &then])
+            .arg(git_dir.join(file))
+            .status()
+            .unwrap();
+        assert!(status.success());
+    }
+}
+
+/// A clone with a merged, a gone, an idle and an active worktree
+fn setup(parent: &Path) -> PathBuf {
+    let upstream = init_repo(parent, "upstream");
+    git(parent, &["clone", "-q", upstream.to_str().unwrap(), "repo"]);
+    let repo = parent.join("repo");
+    // Let the clone push to main
+    git(&upstream, &["checkout", "-q", "--detach"]);
+
+    let merged = add_worktree(&repo, "merged");
+    commit_file(&merged, "merged.txt", "merged\n", "Merged work");
+    git(&repo, &["merge", "-q", "--ff-only", "merged"]);
+    git(&repo, &["push", "-q", "origin", "main"]);
+
+    let active = add_worktree(&repo, "active");
+    commit_file(&active, "active.txt", "active\n", "Active work");
+    git(&active, &["push", "-q", "-u", "origin", "active"]);
+
+    let gone = add_worktree(&repo, "gone");
+    git(&gone, &["push", "-q", "-u", "origin", "gone"]);
+    git(&upstream, &["branch", "-q", "-D", "gone"]);
+    git(&repo, &["fetch", "-q", "--prune"]);
+
+    let idle = add_worktree(&repo, "idle");
+    make_idle(&repo, &idle, "idle", 40);
+    repo
+}
+
+#[test]
+fn test_clean_dry_run_lists_stale_worktrees() {
+    let dir = tempdir().unwrap();
+    let repo = setup(dir.path());
+
+    let output = run_ok(&mut clean_command(&repo, &["--dry-run"], ""));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let reason = |name: &str| {
+        stdout
+            .lines()
+            .find(|line| line.split_whitespace().next() == Some(name))
+            .map(|line| line.to_string())
+    };
+    assert!(reason("merged").unwrap().contains("merged into main"));
+    assert!(reason("gone").unwrap().contains("origin/gone is gone"));
+    assert!(reason("idle").unwrap().contains("idle for 40 days"));
+    assert!(reason("active").is_none());
+    assert!(reason("repo").is_none());
+
+    let output = clean_command(&repo, &["--dry-run", "--days", "50"], "")
+        .output()
+        .unwrap();
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("idle"));
+    assert_eq!(git(&repo, &["worktree", "list"]).lines().count(), 5);
+}
+
+#[test]
+fn test_clean_yes_removes_all_stale_worktrees() {
+    let dir = tempdir().unwrap();
+    let repo = setup(dir.path());
+
+    run_ok(&mut clean_command(&repo, &["--yes"], ""));
+    for name in ["merged", "gone", "idle"] {
+        assert!(!dir.path().join(name).exists(), "{name} was not removed");
+    }
+    assert!(dir.path().join("active").exists());
+    assert_eq!(git(&repo, &["worktree", "list"]).lines().count(), 2);
+}
+
+#[test]
+fn test_clean_keeps_new_worktree() {
+    let dir = tempdir().unwrap();
+    let repo = setup(dir.path());
+    // Started from main and not worked on yet, so it has main's tip
+    let output = trees(&repo, &["add", "fresh", "--dir-only"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let fresh = PathBuf::from(String::from_utf8_lossy(&output.stdout).trim());
+    assert_eq!(
+        git(&fresh, &["rev-parse", "HEAD"]),
+        git(&repo, &["rev-parse", "main"])
+    );
+
+    run_ok(&mut clean_command(&repo, &["--yes"], ""));
+    assert!(!dir.path().join("merged").exists());
+    assert!(fresh.exists());
+}
+
+#[test]
+fn test_clean_picks_from_list() {
+    let dir = tempdir().unwrap();
+    let repo = setup(dir.path());
+
+    // The fallback picker numbers the candidates on stderr
+    let output = clean_command(&repo, &[], "").output().unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let number = stderr
+        .lines()
+        .find(|line| line.contains(". idle -> "))
+        .and_then(|line| line.split('.').next())
+        .unwrap()
+        .to_string();
+    assert!(stderr.contains("Nothing selected"));
+
+    run_ok(&mut clean_command(&repo, &[], &format!("{number}\n")));
+    assert!(!dir.path().join("idle").exists());
+    assert!(dir.path().join("merged").exists());
+    assert!(dir.path().join("gone").exists());
+}
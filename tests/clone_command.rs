@@ -0,0 +1,108 @@
+use std::path::Path;
+use tempfile::tempdir;
+
+mod common;
+use common::{commit_file, git, init_repo, stdout_ok, trees};
+
+fn trees_clone(parent: &Path, args: &[&str]) -> String {
+    let output = stdout_ok(trees(parent, &["clone"]).args(args).arg("--dir-only"));
+    output.lines().last().unwrap_or_default().to_string()
+}
+
+#[test]
+fn test_clone_sets_up_bare_layout() {
+    let dir = tempdir().unwrap();
+    let upstream = init_repo(dir.path(), "upstream");
+    git(&upstream, &["checkout", "-q", "-b", "feature"]);
+    commit_file(&upstream, "feature.txt", "feature\n", "Feature work");
+    git(&upstream, &["checkout", "-q", "main"]);
+    let url = format!("file://{}", upstream.display());
+
+    let workspace = dir.path().join("workspace");
+    std::fs::create_dir_all(&workspace).unwrap();
+    let path = trees_clone(&workspace, &[&url]);
+
+    let project = workspace.join("upstream");
+    assert_eq!(
+        path,
+        project
+            .join("main")
+            .canonicalize()
+            .unwrap()
+            .to_string_lossy()
+    );
+    assert_eq!(
+        std::fs::read_to_string(project.join(".git")).unwrap(),
+        "gitdir: ./.bare\n"
+    );
+    let bare = project.join(".bare");
+    assert_eq!(git(&bare, &["rev-parse", "--is-bare-repository"]), "true");
+    assert_eq!(
+        git(&bare, &["config", "--get", "remote.origin.fetch"]),
+        "+refs/heads/*:refs/remotes/origin/*"
+    );
+    assert_eq!(
+        git(&bare, &["rev-parse", "origin/feature"]),
+        git(&upstream, &["rev-parse", "feature"])
+    );
+
+    let main = project.join("main");
+    assert!(main.join("README.md").exists());
+    assert_eq!(
+        git(&main, &["rev-parse", "--abbrev-ref", "@{upstream}"]),
+        "origin/main"
+    );
+
+    // The new layout works with the rest of trees
+    let output = trees(
+        &project,
+        &["add", "feature", "--track", "origin/feature", "--dir-only"],
+    )
+    .output()
+    .unwrap();
+    assert!(output.status.success());
+    assert!(project.join("feature").join("feature.txt").exists());
+}
+
+#[test]
+fn test_clone_with_name_and_other_default_branch() {
+    let dir = tempdir().unwrap();
+    let upstream = init_repo(dir.path(), "upstream");
+    git(&upstream, &["checkout", "-q", "-b", "trunk"]);
+
+    let path = trees_clone(dir.path(), &[upstream.to_str().unwrap(), "checkout"]);
+
+    let worktree = dir.path().join("checkout").join("trunk");
+    assert_eq!(path, worktree.canonicalize().unwrap().to_string_lossy());
+    assert_eq!(git(&worktree, &["branch", "--show-current"]), "trunk");
+}
+
+#[test]
+fn test_clone_refuses_existing_directory() {
+    let dir = tempdir().unwrap();
+    let upstream = init_repo(dir.path(), "upstream");
+
+    trees(dir.path(), &["clone", upstream.to_str().unwrap()])
+        .assert()
+        .failure();
+}
+
+#[test]
+fn test_clone_failure_leaves_nothing_behind() {
+    let dir = tempdir().unwrap();
+    let upstream = dir.path().join("upstream");
+
+    let output = trees(
+        dir.path(),
+        &["clone", upstream.to_str().unwrap(), "project"],
+    )
+    .output()
+    .unwrap();
+    assert!(!output.status.success());
+    assert!(!dir.path().join("project").exists());
+
+    // So the same clone works once the remote is there
+    init_repo(dir.path(), "upstream");
+    let path = trees_clone(dir.path(), &[upstream.to_str().unwrap(), "project"]);
+    assert!(Path::new(&path).join("README.md").exists());
+}
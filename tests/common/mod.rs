@@ -0,0 +1,94 @@
+#![allow(dead_code)]
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+/// Run a git command in `dir`, panicking if it fails, and return its stdout
+pub fn git(dir: &Path, args: &[&str]) -> String {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .env("GIT_AUTHOR_NAME", "Test User")
+        .env("GIT_AUTHOR_EMAIL", "test@example.com")
+        .env("GIT_COMMITTER_NAME", "Test User")
+        .env("GIT_COMMITTER_EMAIL", "test@example.com")
+        .output()
+        .expect("Failed to run git");
+    assert!(
+        output.status.success(),
+        "git {:?} failed: {}",
+        args,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8_lossy(&output.stdout).trim().to_string()
+}
+
+/// Write `contents` to `file` in `dir` and commit it
+pub fn commit_file(dir: &Path, file: &str, contents: &str, message: &str) {
+    std::fs::write(dir.join(file), contents).unwrap();
+    git(dir, &["add", file]);
+    git(dir, &["commit", "-m", message]);
+}
+
+/// Initialize a repository named `name` inside `parent` with one commit on `main`
+pub fn init_repo(parent: &Path, name: &str) -> PathBuf {
+    let repo = parent.join(name);
+    std::fs::create_dir_all(&repo).unwrap();
+    git(&repo, &["init", "-b", "main"]);
+    commit_file(&repo, "README.md", "# Test Repository\n", "Initial commit");
+    repo
+}
+
+/// A `trees-bin` command running `args` on the repository at `repo`. Add
+/// environment, stdin or a working directory before running it.
+pub fn trees(repo: &Path, args: &[&str]) -> assert_cmd::Command {
+    let mut command = assert_cmd::Command::cargo_bin("trees-bin").unwrap();
+    command.arg("--path").arg(repo).args(args);
+    command
+}
+
+/// Run `command`, panicking with its stderr if it fails, and return its output
+pub fn run_ok(command: &mut assert_cmd::Command) -> Output {
+    let output = command.output().unwrap();
+    assert!(
+        output.status.success(),
+        "{command:?} failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    output
+}
+
+/// The stdout of a successful `command`
+pub fn stdout_ok(command: &mut assert_cmd::Command) -> String {
+    String::from_utf8_lossy(&run_ok(command).stdout).to_string()
+}
+
+/// Add a worktree for a new branch `name` next to `repo`, in `<parent>/<name>`
+pub fn add_worktree(repo: &Path, name: &str) -> PathBuf {
+    let path = repo.parent().unwrap().join(name);
+    git(
+        repo,
+        &["worktree", "add", "-q", "-b", name, path.to_str().unwrap()],
+    );
+    path
+}
+
+/// The commit `rev` resolves to in `dir`
+pub fn rev(dir: &Path, rev: &str) -> String {
+    git(dir, &["rev-parse", rev])
+}
+
+/// The RESULT column of the summary row for worktree `name` in the output of
+/// `trees pull` or `trees sync`
+pub fn result_for(output: &str, name: &str) -> String {
+    assert!(
+        output.lines().any(|line| line.starts_with("NAME")),
+        "no summary table in:\n{output}"
+    );
+    output
+        .lines()
+        .find(|line| line.split_whitespace().next() == Some(name))
+        .and_then(|line| line.split_whitespace().nth(2))
+        .unwrap_or_else(|| panic!("no result for {name} in:\n{output}"))
+        .to_string()
+}
@@ -0,0 +1,91 @@
+use std::path::Path;
+use tempfile::tempdir;
+
+mod common;
+use common::{commit_file, git, init_repo, stdout_ok, trees};
+
+fn trees_conflicts(repo: &Path, args: &[&str]) -> String {
+    stdout_ok(trees(repo, &["conflicts"]).args(args))
+}
+
+/// Worktrees `a` and `b` both change README.md, `c` adds a file of its own
+fn setup(parent: &Path) -> std::path::PathBuf {
+    let repo = init_repo(parent, "repo");
+    for (name, file, contents) in [
+        ("a", "README.md", "a\n"),
+        ("b", "README.md", "b\n"),
+        ("c", "c.txt", "c\n"),
+    ] {
+        let path = parent.join(name);
+        git(
+            &repo,
+            &["worktree", "add", "-q", "-b", name, path.to_str().unwrap()],
+        );
+        commit_file(&path, file, contents, &format!("Change {file} on {name}"));
+    }
+    repo
+}
+
+/// The cell in the row for `row` and the column for `column`
+fn cell(output: &str, row: &str, column: &str) -> String {
+    let header: Vec<&str> = output.lines().next().unwrap().split_whitespace().collect();
+    let index = header.iter().position(|name| *name == column).unwrap();
+    let line = output
+        .lines()
+        .skip(1)
+        .find(|line| line.split_whitespace().next() == Some(row))
+        .unwrap();
+    // Cells are separated by at least two spaces; "conflict (1)" has one
+    let cells: Vec<&str> = line
+        .split("  ")
+        .map(str::trim)
+        .filter(|cell| !cell.is_empty())
+        .skip(1)
+        .collect();
+    cells[index].to_string()
+}
+
+#[test]
+fn test_conflicts_between_every_pair() {
+    let dir = tempdir().unwrap();
+    let repo = setup(dir.path());
+
+    let output = trees_conflicts(&repo, &[]);
+    let mut header: Vec<&str> = output.lines().next().unwrap().split_whitespace().collect();
+    header.sort();
+    assert_eq!(header, ["a", "b", "c", "main"]);
+    for name in header {
+        assert_eq!(cell(&output, name, name), "-");
+        assert_eq!(
+            cell(&output, name, "main"),
+            if name == "main" { "-" } else { "clean" }
+        );
+        assert_eq!(
+            cell(&output, name, "c"),
+            if name == "c" { "-" } else { "clean" }
+        );
+    }
+    assert_eq!(cell(&output, "a", "b"), "conflict (1)");
+    assert_eq!(cell(&output, "b", "a"), "conflict (1)");
+    let pair = output.lines().find(|line| line.contains("<->")).unwrap();
+    assert!(pair == "a <-> b: README.md" || pair == "b <-> a: README.md");
+    assert_eq!(output.matches("<->").count(), 1);
+}
+
+#[test]
+fn test_conflicts_against_default_branch() {
+    let dir = tempdir().unwrap();
+    let repo = setup(dir.path());
+    commit_file(&repo, "README.md", "main\n", "Change README.md on main");
+
+    let output = trees_conflicts(&repo, &["--default-branch"]);
+    let header: Vec<&str> = output.lines().next().unwrap().split_whitespace().collect();
+    assert_eq!(header, ["main"]);
+    assert_eq!(cell(&output, "a", "main"), "conflict (1)");
+    assert_eq!(cell(&output, "b", "main"), "conflict (1)");
+    assert_eq!(cell(&output, "c", "main"), "clean");
+    assert!(output.lines().any(|line| line == "a <-> main: README.md"));
+    assert!(output.lines().any(|line| line == "b <-> main: README.md"));
+    // The default branch's own worktree isn't compared with itself
+    assert!(!output.lines().skip(1).any(|line| line.starts_with("main ")));
+}
@@ -0,0 +1,174 @@
+use std::path::{Path, PathBuf};
+use tempfile::tempdir;
+
+mod common;
+use common::{commit_file, git, init_repo, run_ok, trees};
+
+/// A clone on `feature` with staged, unstaged and untracked changes and a stash
+fn busy_clone(parent: &Path) -> PathBuf {
+    let upstream = init_repo(parent, "upstream");
+    git(&upstream, &["branch", "feature"]);
+    git(parent, &["clone", "-q", upstream.to_str().unwrap(), "repo"]);
+    let repo = parent.join("repo");
+    git(&repo, &["checkout", "-q", "feature"]);
+    git(&repo, &["branch", "local-only"]);
+    commit_file(&repo, "tracked.txt", "one\n", "Add tracked file");
+
+    std::fs::write(repo.join("tracked.txt"), "stashed\n").unwrap();
+    git(&repo, &["stash", "-q"]);
+    std::fs::write(repo.join("staged.txt"), "staged\n").unwrap();
+    git(&repo, &["add", "staged.txt"]);
+    std::fs::write(repo.join("tracked.txt"), "unstaged\n").unwrap();
+    std::fs::write(repo.join("untracked.txt"), "untracked\n").unwrap();
+    repo
+}
+
+fn trees_convert(repo: &Path, args: &[&str]) -> String {
+    let output = run_ok(trees(repo, &["convert"]).args(args));
+    // Only the plan of a dry run goes to stdout; progress is a diagnostic
+    String::from_utf8_lossy(&output.stdout).to_string()
+}
+
+#[test]
+fn test_convert_keeps_changes_stashes_and_upstreams() {
+    let dir = tempdir().unwrap();
+    let repo = busy_clone(dir.path());
+    let status_before = git(&repo, &["status", "--porcelain"]);
+
+    trees_convert(&repo, &[]);
+
+    let bare = repo.join(".bare");
+    assert_eq!(git(&bare, &["rev-parse", "--is-bare-repository"]), "true");
+    assert_eq!(
+        std::fs::read_to_string(repo.join(".git")).unwrap(),
+        "gitdir: ./.bare\n"
+    );
+    let worktree = repo.join("feature");
+    assert_eq!(git(&worktree, &["status", "--porcelain"]), status_before);
+    assert_eq!(git(&worktree, &["branch", "--show-current"]), "feature");
+    assert_eq!(git(&worktree, &["stash", "list"]).lines().count(), 1);
+    assert_eq!(
+        git(
+            &worktree,
+            &["rev-parse", "--abbrev-ref", "feature@{upstream}"]
+        ),
+        "origin/feature"
+    );
+    assert!(!git(&bare, &["branch", "--list", "local-only"]).is_empty());
+}
+
+#[test]
+fn test_convert_dry_run_changes_nothing() {
+    let dir = tempdir().unwrap();
+    let repo = busy_clone(dir.path());
+    let status_before = git(&repo, &["status", "--porcelain"]);
+
+    // `--quiet` silences progress, not the plan that was asked for
+    let plan = trees_convert(&repo, &["--dry-run", "--quiet"]);
+
+    assert!(plan.contains(".bare"));
+    assert!(plan.contains("branch 'feature'"));
+    assert!(repo.join(".git").is_dir());
+    assert!(!repo.join(".bare").exists());
+    assert_eq!(git(&repo, &["status", "--porcelain"]), status_before);
+}
+
+#[test]
+fn test_convert_repoints_linked_worktrees() {
+    let dir = tempdir().unwrap();
+    let repo = init_repo(dir.path(), "repo");
+    let linked = dir.path().join("linked");
+    git(
+        &repo,
+        &[
+            "worktree",
+            "add",
+            "-q",
+            "-b",
+            "side",
+            linked.to_str().unwrap(),
+        ],
+    );
+
+    trees_convert(&repo, &[]);
+
+    assert_eq!(git(&linked, &["branch", "--show-current"]), "side");
+    let list = git(&repo.join("main"), &["worktree", "list"]);
+    assert!(list.contains("linked"), "unexpected worktree list:\n{list}");
+}
+
+#[test]
+fn test_convert_refuses_detached_head() {
+    let dir = tempdir().unwrap();
+    let repo = init_repo(dir.path(), "repo");
+    git(&repo, &["checkout", "-q", "--detach"]);
+
+    trees(&repo, &["convert"]).assert().failure();
+    assert!(repo.join(".git").is_dir());
+}
+
+fn trees_convert_failing(repo: &Path, args: &[&str]) -> String {
+    let output = trees(repo, &["convert"]).args(args).output().unwrap();
+    assert!(!output.status.success());
+    String::from_utf8_lossy(&output.stderr).to_string()
+}
+
+#[test]
+fn test_convert_refuses_worktree_name_collision() {
+    let dir = tempdir().unwrap();
+    let repo = busy_clone(dir.path());
+    let status_before = git(&repo, &["status", "--porcelain"]);
+    // Its admin directory is named after the last path component
+    let linked = dir.path().join("elsewhere").join("feature");
+    git(
+        &repo,
+        &[
+            "worktree",
+            "add",
+            "-q",
+            "-b",
+            "other",
+            linked.to_str().unwrap(),
+        ],
+    );
+
+    for args in [&["--dry-run"][..], &[]] {
+        let stderr = trees_convert_failing(&repo, args);
+        assert!(stderr.contains("is named 'feature'"), "{stderr}");
+        assert!(repo.join(".git").is_dir());
+        assert!(!repo.join(".bare").exists());
+        assert_eq!(git(&repo, &["status", "--porcelain"]), status_before);
+    }
+}
+
+#[test]
+fn test_convert_failure_puts_clone_back() {
+    let dir = tempdir().unwrap();
+    let repo = busy_clone(dir.path());
+    let status_before = git(&repo, &["status", "--porcelain"]);
+    // With `feature` checked out a second time, creating its new worktree
+    // fails after the working tree has been moved aside
+    let linked = dir.path().join("linked");
+    git(
+        &repo,
+        &[
+            "worktree",
+            "add",
+            "-q",
+            "-f",
+            linked.to_str().unwrap(),
+            "feature",
+        ],
+    );
+
+    let stderr = trees_convert_failing(&repo, &[]);
+    assert!(stderr.contains("put back as it was"), "{stderr}");
+    assert!(repo.join(".git").is_dir());
+    assert!(!repo.join(".bare").exists());
+    assert!(!repo.join(".trees-convert").exists());
+    assert!(!repo.join("feature").exists());
+    assert_eq!(git(&repo, &["rev-parse", "--is-bare-repository"]), "false");
+    assert_eq!(git(&repo, &["status", "--porcelain"]), status_before);
+    assert_eq!(git(&repo, &["stash", "list"]).lines().count(), 1);
+    assert_eq!(git(&linked, &["branch", "--show-current"]), "feature");
+}
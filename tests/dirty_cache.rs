@@ -0,0 +1,105 @@
+use std::path::Path;
+use tempfile::tempdir;
+
+mod common;
+use common::{add_worktree, git, init_repo, run_ok, trees};
+
+/// Map of worktree name to `is_dirty` from `trees list --format json`
+fn dirty_flags(repo: &Path) -> Vec<(String, bool)> {
+    let output = run_ok(&mut trees(repo, &["list", "--format", "json"]));
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    json["worktrees"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|wt| {
+            (
+                wt["name"].as_str().unwrap().to_string(),
+                wt["is_dirty"].as_bool().unwrap(),
+            )
+        })
+        .collect()
+}
+
+fn setup(parent: &Path) -> std::path::PathBuf {
+    let repo = init_repo(parent, "repo");
+    for name in ["one", "two", "three"] {
+        add_worktree(&repo, name);
+    }
+    repo
+}
+
+#[test]
+fn test_dirty_checks_across_worktrees() {
+    let dir = tempdir().unwrap();
+    let repo = setup(dir.path());
+    std::fs::write(dir.path().join("two").join("new.txt"), "new\n").unwrap();
+
+    let flags = dirty_flags(&repo);
+    let expected = [
+        ("main", false),
+        ("one", false),
+        ("two", true),
+        ("three", false),
+    ];
+    let expected: Vec<(String, bool)> = expected
+        .iter()
+        .map(|(name, dirty)| (name.to_string(), *dirty))
+        .collect();
+    assert_eq!(flags, expected);
+    assert!(repo.join(".git").join("trees-cache.json").exists());
+}
+
+#[test]
+fn test_list_sees_edits_to_tracked_files() {
+    let dir = tempdir().unwrap();
+    let repo = setup(dir.path());
+    let one = dir.path().join("one");
+    assert!(dirty_flags(&repo).contains(&("one".to_string(), false)));
+
+    // Neither the index nor HEAD changes, but list checks afresh
+    std::fs::write(one.join("README.md"), "changed\n").unwrap();
+    assert!(dirty_flags(&repo).contains(&("one".to_string(), true)));
+}
+
+/// The `(dirty)` marker on worktree `name` in the fallback picker
+fn picker_marks_dirty(repo: &Path, name: &str) -> bool {
+    let output = trees(repo, &[]).env("TERM", "dumb").output().unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let line = stderr
+        .lines()
+        .find(|line| line.contains(&format!(". {name} -> ")))
+        .unwrap_or_else(|| panic!("no option for {name} in:\n{stderr}"));
+    line.ends_with("(dirty)")
+}
+
+#[test]
+fn test_picker_rechecks_changed_index() {
+    let dir = tempdir().unwrap();
+    let repo = setup(dir.path());
+    let one = dir.path().join("one");
+    std::fs::write(dir.path().join("two").join("new.txt"), "new\n").unwrap();
+
+    // Nothing cached yet, so every worktree is checked
+    assert!(!picker_marks_dirty(&repo, "one"));
+    assert!(picker_marks_dirty(&repo, "two"));
+    assert!(repo.join(".git").join("trees-cache.json").exists());
+
+    // Staging a file changes the index, which invalidates the cached marker
+    std::fs::write(one.join("staged.txt"), "staged\n").unwrap();
+    git(&one, &["add", "staged.txt"]);
+    assert!(picker_marks_dirty(&repo, "one"));
+}
+
+#[test]
+fn test_dirty_cache_drops_removed_worktrees() {
+    let dir = tempdir().unwrap();
+    let repo = setup(dir.path());
+    dirty_flags(&repo);
+    git(&repo, &["worktree", "remove", "../three"]);
+    dirty_flags(&repo);
+
+    let cache = std::fs::read_to_string(repo.join(".git").join("trees-cache.json")).unwrap();
+    assert!(cache.contains("two"));
+    assert!(!cache.contains("three"), "stale entry in cache: {cache}");
+}
@@ -0,0 +1,93 @@
+use std::path::{Path, PathBuf};
+use tempfile::tempdir;
+
+mod common;
+use common::{commit_file, git, init_repo, run_ok, trees};
+
+fn trees_fetch(repo: &Path, args: &[&str]) -> (String, String) {
+    let output = run_ok(trees(repo, &["fetch"]).args(args));
+    (
+        String::from_utf8_lossy(&output.stdout).to_string(),
+        String::from_utf8_lossy(&output.stderr).to_string(),
+    )
+}
+
+/// A clone with a worktree on `doomed`, after upstream added `fresh`, deleted
+/// `doomed`, rewrote `rewrite` and fast-forwarded `keep`
+fn setup(parent: &Path) -> (PathBuf, PathBuf, PathBuf) {
+    let upstream = init_repo(parent, "upstream");
+    for branch in ["keep", "doomed", "rewrite"] {
+        git(&upstream, &["branch", branch]);
+    }
+    git(&upstream, &["checkout", "-q", "rewrite"]);
+    commit_file(&upstream, "rewrite.txt", "one\n", "To be rewritten");
+    git(&upstream, &["checkout", "-q", "main"]);
+    git(parent, &["clone", "-q", upstream.to_str().unwrap(), "repo"]);
+    let repo = parent.join("repo");
+    let doomed = parent.join("doomed");
+    git(
+        &repo,
+        &[
+            "worktree",
+            "add",
+            "-q",
+            "--track",
+            "-b",
+            "doomed",
+            doomed.to_str().unwrap(),
+            "origin/doomed",
+        ],
+    );
+
+    git(&upstream, &["branch", "fresh"]);
+    git(&upstream, &["branch", "-D", "doomed"]);
+    git(&upstream, &["checkout", "-q", "rewrite"]);
+    git(&upstream, &["reset", "-q", "--hard", "HEAD~1"]);
+    commit_file(&upstream, "rewrite.txt", "two\n", "Rewritten");
+    git(&upstream, &["checkout", "-q", "keep"]);
+    commit_file(&upstream, "keep.txt", "keep\n", "Fast-forward");
+    git(&upstream, &["checkout", "-q", "main"]);
+    (upstream, repo, doomed)
+}
+
+#[test]
+fn test_fetch_prune_reports_changes() {
+    let dir = tempdir().unwrap();
+    let (upstream, repo, doomed) = setup(dir.path());
+    let old_rewrite = git(&repo, &["rev-parse", "--short=8", "origin/rewrite"]);
+    let head = git(&doomed, &["rev-parse", "HEAD"]);
+
+    let (stdout, _) = trees_fetch(&repo, &["--prune"]);
+    let new_rewrite = git(&upstream, &["rev-parse", "--short=8", "rewrite"]);
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(
+        lines,
+        [
+            "deleted origin/doomed".to_string(),
+            "new     origin/fresh".to_string(),
+            format!("forced  origin/rewrite {old_rewrite}...{new_rewrite}"),
+            "gone    origin/doomed (worktree 'doomed')".to_string(),
+        ]
+    );
+
+    // No checkout is touched
+    assert_eq!(git(&doomed, &["rev-parse", "HEAD"]), head);
+    assert!(!repo.join("keep.txt").exists());
+}
+
+#[test]
+fn test_fetch_without_prune_keeps_deleted_branches() {
+    let dir = tempdir().unwrap();
+    let (_upstream, repo, _doomed) = setup(dir.path());
+
+    let (stdout, _) = trees_fetch(&repo, &[]);
+    assert!(stdout.contains("new     origin/fresh"));
+    assert!(!stdout.contains("deleted"));
+    assert!(!stdout.contains("gone"));
+    git(&repo, &["rev-parse", "--verify", "-q", "origin/doomed"]);
+
+    // A second fetch has nothing new to report
+    let (stdout, stderr) = trees_fetch(&repo, &[]);
+    assert_eq!(stdout, "");
+    assert!(stderr.contains("No remote branches changed"));
+}
@@ -0,0 +1,197 @@
+use std::path::{Path, PathBuf};
+use tempfile::tempdir;
+
+mod common;
+use common::{commit_file, git, init_repo, run_ok, trees};
+
+fn land_command(repo: &Path, cwd: &Path, args: &[&str]) -> assert_cmd::Command {
+    let mut command = trees(repo, &["land"]);
+    command.args(args).env("TERM", "dumb").current_dir(cwd);
+    command
+}
+
+/// A clone of a bare upstream. The clone's `feature` worktree has a commit,
+/// while upstream main has moved on by a commit of its own.
+fn setup(parent: &Path) -> (PathBuf, PathBuf, PathBuf) {
+    let seed = init_repo(parent, "seed");
+    git(parent, &["clone", "-q", "--bare", "seed", "upstream.git"]);
+    let upstream = parent.join("upstream.git");
+    git(parent, &["clone", "-q", "upstream.git", "repo"]);
+    let repo = parent.join("repo");
+    git(&repo, &["config", "user.name", "Test User"]);
+    git(&repo, &["config", "user.email", "test@example.com"]);
+
+    let feature = parent.join("feature");
+    git(
+        &repo,
+        &[
+            "worktree",
+            "add",
+            "-q",
+            "-b",
+            "feature",
+            feature.to_str().unwrap(),
+        ],
+    );
+    commit_file(&feature, "feature.txt", "feature\n", "Add feature");
+    commit_file(&seed, "upstream.txt", "upstream\n", "Add upstream");
+    git(&seed, &["push", "-q", upstream.to_str().unwrap(), "main"]);
+    (upstream, repo, feature)
+}
+
+fn assert_landed(upstream: &Path, repo: &Path, feature: &Path) {
+    assert!(!feature.exists());
+    assert_eq!(git(repo, &["branch", "--list", "feature"]), "");
+    assert_eq!(
+        git(repo, &["rev-parse", "main"]),
+        git(upstream, &["rev-parse", "main"])
+    );
+    // Rebased on top of upstream: linear, with both commits
+    assert_eq!(
+        git(repo, &["log", "--format=%s", "-2", "main"]),
+        "Add feature\nAdd upstream"
+    );
+    assert_eq!(git(repo, &["rev-list", "--merges", "main"]), "");
+    assert!(repo.join("feature.txt").exists());
+    assert!(!repo.join(".git/trees-land.json").exists());
+}
+
+#[test]
+fn test_land_named_worktree() {
+    let dir = tempdir().unwrap();
+    let (upstream, repo, feature) = setup(dir.path());
+
+    let output = run_ok(&mut land_command(&repo, dir.path(), &["feature"]));
+    assert_landed(&upstream, &repo, &feature);
+    // The default branch's worktree is printed for the shell wrapper
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(
+        Path::new(stdout.trim()).canonicalize().unwrap(),
+        repo.canonicalize().unwrap()
+    );
+}
+
+#[test]
+fn test_land_current_worktree() {
+    let dir = tempdir().unwrap();
+    let (upstream, repo, feature) = setup(dir.path());
+
+    run_ok(&mut land_command(&repo, &feature, &[]));
+    assert_landed(&upstream, &repo, &feature);
+}
+
+#[test]
+fn test_land_refuses_default_branch_and_dirty_worktrees() {
+    let dir = tempdir().unwrap();
+    let (_, repo, feature) = setup(dir.path());
+
+    let output = land_command(&repo, dir.path(), &["main"]).output().unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("default branch's worktree"));
+
+    std::fs::write(feature.join("feature.txt"), "changed\n").unwrap();
+    let output = land_command(&repo, dir.path(), &["feature"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("uncommitted changes"));
+    assert!(feature.exists());
+    assert!(!repo.join(".git/trees-land.json").exists());
+}
+
+#[test]
+fn test_land_continue_after_failed_push() {
+    let dir = tempdir().unwrap();
+    let (upstream, repo, feature) = setup(dir.path());
+    let missing = dir.path().join("missing");
+    git(
+        &repo,
+        &["config", "remote.origin.pushurl", missing.to_str().unwrap()],
+    );
+
+    let output = land_command(&repo, dir.path(), &["feature"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("stopped at step 'push'"), "{stderr}");
+    assert!(stderr.contains("trees land --continue"));
+    assert!(repo.join(".git/trees-land.json").exists());
+    // The merge went through; the worktree is still there
+    assert!(repo.join("feature.txt").exists());
+    assert!(feature.exists());
+
+    // Nothing else can be landed meanwhile
+    let output = land_command(&repo, dir.path(), &["feature"])
+        .output()
+        .unwrap();
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Already landing 'feature'"));
+
+    git(&repo, &["config", "--unset", "remote.origin.pushurl"]);
+    run_ok(&mut land_command(&repo, dir.path(), &["--continue"]));
+    assert_landed(&upstream, &repo, &feature);
+}
+
+#[test]
+fn test_land_abort() {
+    let dir = tempdir().unwrap();
+    let (_, repo, feature) = setup(dir.path());
+    let missing = dir.path().join("missing");
+    git(
+        &repo,
+        &["config", "remote.origin.pushurl", missing.to_str().unwrap()],
+    );
+    land_command(&repo, dir.path(), &["feature"])
+        .assert()
+        .failure();
+
+    run_ok(&mut land_command(&repo, dir.path(), &["--abort"]));
+    assert!(!repo.join(".git/trees-land.json").exists());
+    assert!(feature.exists());
+
+    let output = land_command(&repo, dir.path(), &["--continue"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("No landing in progress"));
+}
+
+#[test]
+fn test_land_delete_remote_keeps_default_branch() {
+    let dir = tempdir().unwrap();
+    let (upstream, repo, _) = setup(dir.path());
+    // Started from origin/main, so it tracks the branch it lands on
+    let feat = dir.path().join("feat");
+    git(
+        &repo,
+        &[
+            "worktree",
+            "add",
+            "-q",
+            "-b",
+            "feat",
+            feat.to_str().unwrap(),
+            "origin/main",
+        ],
+    );
+    commit_file(&feat, "feat.txt", "feat\n", "Add feat");
+
+    let output = run_ok(&mut land_command(
+        &repo,
+        dir.path(),
+        &["feat", "--delete-remote"],
+    ));
+    assert!(String::from_utf8_lossy(&output.stderr).contains(
+        "Branch 'feat' tracks 'origin/main', not a remote branch of its own; not deleting it on 'origin'"
+    ));
+    assert!(!feat.exists());
+    assert_eq!(git(&repo, &["branch", "--list", "feat"]), "");
+    assert_eq!(
+        git(&upstream, &["rev-parse", "main"]),
+        git(&repo, &["rev-parse", "main"])
+    );
+    assert_eq!(
+        git(&upstream, &["log", "-1", "--format=%s", "main"]),
+        "Add feat"
+    );
+}
@@ -0,0 +1,114 @@
+use std::path::Path;
+use tempfile::tempdir;
+
+mod common;
+use common::{commit_file, git, init_repo, stdout_ok, trees};
+
+fn trees_list(path: &Path, format: &str) -> String {
+    stdout_ok(&mut trees(path, &["list", "--format", format]))
+}
+
+/// A clone whose `main` is one commit ahead of and behind `origin/main`, with
+/// a locked linked worktree on `feature`
+fn setup(parent: &Path) -> std::path::PathBuf {
+    let upstream = init_repo(parent, "upstream");
+    git(parent, &["clone", "-q", upstream.to_str().unwrap(), "repo"]);
+    let repo = parent.join("repo");
+    commit_file(&upstream, "theirs.txt", "theirs\n", "Upstream work");
+    git(&repo, &["fetch", "-q"]);
+    commit_file(&repo, "ours.txt", "ours\n", "Local work");
+    let feature = parent.join("feature");
+    git(
+        &repo,
+        &[
+            "worktree",
+            "add",
+            "-q",
+            "-b",
+            "feature",
+            feature.to_str().unwrap(),
+        ],
+    );
+    git(
+        &repo,
+        &["worktree", "lock", "--reason", "in use", "../feature"],
+    );
+    std::fs::write(feature.join("dirty.txt"), "dirty\n").unwrap();
+    repo
+}
+
+#[test]
+fn test_list_json() {
+    let dir = tempdir().unwrap();
+    let repo = setup(dir.path());
+
+    let output = trees_list(&repo, "json");
+    let json: serde_json::Value = serde_json::from_str(&output).unwrap();
+
+    assert_eq!(json["version"], 1);
+    let worktrees = json["worktrees"].as_array().unwrap();
+    assert_eq!(worktrees.len(), 2);
+
+    let main = &worktrees[0];
+    assert_eq!(main["name"], "main");
+    assert_eq!(main["path"], repo.canonicalize().unwrap().to_str().unwrap());
+    assert_eq!(main["branch"], "main");
+    assert_eq!(main["detached"], false);
+    assert_eq!(main["is_dirty"], false);
+    assert_eq!(main["is_current"], true);
+    assert_eq!(main["state"], "active");
+    assert_eq!(main["head"], git(&repo, &["rev-parse", "HEAD"]));
+    assert_eq!(main["upstream"], "origin/main");
+    assert_eq!(main["ahead"], 1);
+    assert_eq!(main["behind"], 1);
+
+    let feature = &worktrees[1];
+    assert_eq!(feature["branch"], "feature");
+    assert_eq!(feature["is_dirty"], true);
+    assert_eq!(feature["is_current"], false);
+    assert_eq!(feature["state"], "locked");
+    assert_eq!(feature["lock_reason"], "in use");
+    assert!(feature["upstream"].is_null());
+    assert!(feature["ahead"].is_null());
+}
+
+#[test]
+fn test_list_porcelain() {
+    let dir = tempdir().unwrap();
+    let repo = setup(dir.path());
+    let feature = dir.path().join("feature").canonicalize().unwrap();
+
+    let output = trees_list(&feature, "porcelain");
+    let records: Vec<&str> = output.split("\n\n").collect();
+
+    assert_eq!(records[0], "version 1");
+    let main: Vec<&str> = records[1].lines().collect();
+    assert_eq!(
+        main[0],
+        format!("worktree {}", repo.canonicalize().unwrap().display())
+    );
+    assert!(main.contains(&"name main"));
+    assert!(main.contains(&"branch refs/heads/main"));
+    assert!(main.contains(&"upstream origin/main"));
+    assert!(main.contains(&"ahead 1"));
+    assert!(main.contains(&"behind 1"));
+    assert!(!main.contains(&"current"));
+
+    let worktree: Vec<&str> = records[2].lines().collect();
+    assert_eq!(worktree[0], format!("worktree {}", feature.display()));
+    assert!(worktree.contains(&"branch refs/heads/feature"));
+    assert!(worktree.contains(&"dirty"));
+    assert!(worktree.contains(&"locked in use"));
+    assert!(worktree.contains(&"current"));
+}
+
+#[test]
+fn test_list_text_has_no_trailing_space() {
+    let dir = tempdir().unwrap();
+    let repo = init_repo(dir.path(), "repo");
+    git(&repo, &["checkout", "-q", "--detach"]);
+
+    let output = trees_list(&repo, "text");
+    let line = output.lines().next().unwrap();
+    assert!(!line.ends_with(' '), "trailing space in {line:?}");
+}
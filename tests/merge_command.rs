@@ -0,0 +1,300 @@
+use std::path::{Path, PathBuf};
+use tempfile::tempdir;
+
+mod common;
+use common::{commit_file, git, init_repo, rev, run_ok, trees};
+
+/// Merge the second worktree in the selector (feature) into the first (main)
+fn merge_command(repo: &Path, args: &[&str]) -> assert_cmd::Command {
+    merge_answering(repo, args, "")
+}
+
+/// Like `merge_command`, then answer the conflict prompts with `answers`
+fn merge_answering(repo: &Path, args: &[&str], answers: &str) -> assert_cmd::Command {
+    let mut command = trees(repo, &["merge"]);
+    command
+        .env("TERM", "dumb")
+        .env("EDITOR", "false")
+        .env_remove("VISUAL")
+        .args(args)
+        .write_stdin(format!("2\n1\n{answers}"));
+    command
+}
+
+/// A repository whose `feature` worktree has two commits on top of main. With
+/// `diverge`, main gets a commit of its own too.
+fn setup(parent: &Path, diverge: bool) -> (PathBuf, PathBuf) {
+    let repo = init_repo(parent, "repo");
+    git(&repo, &["config", "user.name", "Test User"]);
+    git(&repo, &["config", "user.email", "test@example.com"]);
+    let feature = parent.join("feature");
+    git(
+        &repo,
+        &[
+            "worktree",
+            "add",
+            "-q",
+            "-b",
+            "feature",
+            feature.to_str().unwrap(),
+        ],
+    );
+    commit_file(&feature, "one.txt", "one\n", "Add one");
+    commit_file(&feature, "two.txt", "two\n", "Add two");
+    if diverge {
+        commit_file(&repo, "main.txt", "main\n", "Add main");
+    }
+    (repo, feature)
+}
+
+fn parent_count(dir: &Path, rev: &str) -> usize {
+    git(dir, &["rev-list", "--parents", "-n", "1", rev])
+        .split_whitespace()
+        .count()
+        - 1
+}
+
+#[test]
+fn test_ff_only() {
+    let dir = tempdir().unwrap();
+    let (repo, _) = setup(dir.path(), true);
+    let main = rev(&repo, "main");
+
+    let output = merge_command(&repo, &["--strategy", "ff-only"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("have diverged"));
+    assert_eq!(rev(&repo, "main"), main);
+
+    let dir = tempdir().unwrap();
+    let (repo, _) = setup(dir.path(), false);
+    run_ok(&mut merge_command(&repo, &["--strategy", "ff-only"]));
+    assert_eq!(rev(&repo, "main"), rev(&repo, "feature"));
+    assert!(repo.join("two.txt").exists());
+}
+
+#[test]
+fn test_no_ff_with_message() {
+    let dir = tempdir().unwrap();
+    let (repo, _) = setup(dir.path(), false);
+
+    run_ok(&mut merge_command(
+        &repo,
+        &["--strategy", "no-ff", "-m", "Land feature"],
+    ));
+    assert_eq!(parent_count(&repo, "main"), 2);
+    assert_eq!(rev(&repo, "main^2"), rev(&repo, "feature"));
+    assert_eq!(git(&repo, &["log", "-1", "--format=%s"]), "Land feature");
+    assert!(repo.join("two.txt").exists());
+    assert_eq!(git(&repo, &["status", "--porcelain"]), "");
+}
+
+#[test]
+fn test_squash() {
+    let dir = tempdir().unwrap();
+    let (repo, _) = setup(dir.path(), true);
+    let main = rev(&repo, "main");
+
+    run_ok(&mut merge_command(&repo, &["--strategy", "squash"]));
+    assert_eq!(parent_count(&repo, "main"), 1);
+    assert_eq!(rev(&repo, "main^"), main);
+    let message = git(&repo, &["log", "-1", "--format=%B"]);
+    assert!(message.starts_with("Squashed branch 'feature'"));
+    assert!(message.contains("* Add one\n* Add two"));
+    for file in ["one.txt", "two.txt", "main.txt"] {
+        assert!(repo.join(file).exists(), "{file} is missing");
+    }
+    assert_eq!(git(&repo, &["status", "--porcelain"]), "");
+}
+
+#[test]
+fn test_rebase() {
+    let dir = tempdir().unwrap();
+    let (repo, feature) = setup(dir.path(), true);
+    let main = rev(&repo, "main");
+
+    run_ok(&mut merge_command(&repo, &["--strategy", "rebase"]));
+    assert_eq!(rev(&repo, "main"), rev(&repo, "feature"));
+    assert_eq!(rev(&repo, "main~2"), main);
+    assert_eq!(parent_count(&repo, "main"), 1);
+    assert_eq!(parent_count(&repo, "main^"), 1);
+    // Both worktrees are checked out at the new tip
+    assert!(repo.join("two.txt").exists());
+    assert!(feature.join("main.txt").exists());
+    assert_eq!(git(&feature, &["status", "--porcelain"]), "");
+}
+
+#[test]
+fn test_conflict_changes_nothing() {
+    let dir = tempdir().unwrap();
+    let (repo, feature) = setup(dir.path(), false);
+    commit_file(&feature, "README.md", "feature\n", "Change readme");
+    commit_file(&repo, "README.md", "main\n", "Change readme on main");
+    let main = rev(&repo, "main");
+    let feature_tip = rev(&repo, "feature");
+
+    for strategy in ["merge", "squash", "rebase"] {
+        let output = merge_command(&repo, &["--strategy", strategy])
+            .output()
+            .unwrap();
+        assert!(!output.status.success(), "{strategy} should conflict");
+        assert!(String::from_utf8_lossy(&output.stderr).contains("nothing was changed"));
+        assert_eq!(rev(&repo, "main"), main);
+        assert_eq!(rev(&repo, "feature"), feature_tip);
+        assert_eq!(git(&repo, &["status", "--porcelain"]), "");
+        assert!(!repo.join(".git/MERGE_HEAD").exists());
+    }
+}
+
+#[test]
+fn test_message_needs_commit_strategy() {
+    let dir = tempdir().unwrap();
+    let (repo, _) = setup(dir.path(), false);
+    let output = merge_command(&repo, &["--strategy", "ff-only", "-m", "Ignored"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--message only applies"));
+}
+
+#[test]
+fn test_dry_run_reports_without_changing_anything() {
+    let dir = tempdir().unwrap();
+    let (repo, feature) = setup(dir.path(), true);
+    let main = rev(&repo, "main");
+
+    let stdout = |output: std::process::Output| String::from_utf8(output.stdout).unwrap();
+    let output = run_ok(&mut merge_command(&repo, &["--dry-run"]));
+    assert_eq!(stdout(output), "clean\n");
+    run_ok(&mut merge_command(
+        &repo,
+        &["--dry-run", "--strategy", "rebase"],
+    ));
+
+    let output = merge_command(&repo, &["--dry-run", "--strategy", "ff-only"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert_eq!(stdout(output), "diverged\n");
+
+    commit_file(&feature, "main.txt", "feature\n", "Conflict with main");
+    let output = merge_command(&repo, &["--dry-run"]).output().unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("would conflict"));
+    assert_eq!(stdout(output), "conflict main.txt\n");
+
+    assert_eq!(rev(&repo, "main"), main);
+    assert_eq!(git(&repo, &["status", "--porcelain"]), "");
+    assert!(!repo.join("one.txt").exists());
+
+    let dir = tempdir().unwrap();
+    let (repo, _) = setup(dir.path(), false);
+    let output = run_ok(&mut merge_command(&repo, &["--dry-run"]));
+    assert_eq!(stdout(output), "fast-forward\n");
+    assert!(!repo.join("one.txt").exists());
+}
+
+/// Main and feature both change README.md
+fn setup_conflict(parent: &Path) -> PathBuf {
+    let (repo, feature) = setup(parent, false);
+    commit_file(&feature, "README.md", "feature\n", "Change readme");
+    commit_file(&repo, "README.md", "main\n", "Change readme on main");
+    repo
+}
+
+fn readme(repo: &Path) -> String {
+    std::fs::read_to_string(repo.join("README.md")).unwrap()
+}
+
+#[test]
+fn test_resolve_taking_theirs() {
+    let dir = tempdir().unwrap();
+    let repo = setup_conflict(dir.path());
+
+    // Resolve, pick README.md, take theirs, continue
+    run_ok(&mut merge_answering(&repo, &[], "1\n1\n4\n1\n"));
+    assert_eq!(parent_count(&repo, "main"), 2);
+    assert_eq!(rev(&repo, "main^2"), rev(&repo, "feature"));
+    assert_eq!(
+        git(&repo, &["log", "-1", "--format=%s"]),
+        "Merge branch 'feature'"
+    );
+    assert_eq!(readme(&repo), "feature\n");
+    assert!(repo.join("two.txt").exists());
+    assert_eq!(git(&repo, &["status", "--porcelain"]), "");
+    assert!(!repo.join(".git/MERGE_HEAD").exists());
+}
+
+#[test]
+fn test_resolve_squash_taking_ours() {
+    let dir = tempdir().unwrap();
+    let repo = setup_conflict(dir.path());
+    let main = rev(&repo, "main");
+
+    run_ok(&mut merge_answering(
+        &repo,
+        &["--strategy", "squash"],
+        "1\n1\n3\n1\n",
+    ));
+    assert_eq!(parent_count(&repo, "main"), 1);
+    assert_eq!(rev(&repo, "main^"), main);
+    assert!(git(&repo, &["log", "-1", "--format=%s"]).starts_with("Squashed branch 'feature'"));
+    assert_eq!(readme(&repo), "main\n");
+    assert!(repo.join("two.txt").exists());
+    assert_eq!(git(&repo, &["status", "--porcelain"]), "");
+    assert!(!repo.join(".git/SQUASH_MSG").exists());
+}
+
+#[test]
+fn test_resolve_in_editor() {
+    let dir = tempdir().unwrap();
+    let repo = setup_conflict(dir.path());
+    let editor = dir.path().join("editor.sh");
+    std::fs::write(&editor, "printf 'resolved\\n' > \"$1\"\n").unwrap();
+
+    run_ok(
+        trees(&repo, &["merge", "-m", "Merge with edits"])
+            .env("TERM", "dumb")
+            .env("EDITOR", format!("sh {}", editor.display()))
+            .env_remove("VISUAL")
+            .write_stdin("2\n1\n1\n1\n1\n1\n"),
+    );
+    assert_eq!(readme(&repo), "resolved\n");
+    assert_eq!(parent_count(&repo, "main"), 2);
+    assert_eq!(
+        git(&repo, &["log", "-1", "--format=%s"]),
+        "Merge with edits"
+    );
+}
+
+#[test]
+fn test_abort_while_resolving() {
+    let dir = tempdir().unwrap();
+    let repo = setup_conflict(dir.path());
+    let main = rev(&repo, "main");
+
+    run_ok(&mut merge_answering(&repo, &[], "1\n2\n"));
+    assert_eq!(rev(&repo, "main"), main);
+    assert_eq!(readme(&repo), "main\n");
+    assert!(!repo.join("one.txt").exists());
+    assert_eq!(git(&repo, &["status", "--porcelain"]), "");
+    assert!(!repo.join(".git/MERGE_HEAD").exists());
+}
+
+#[test]
+fn test_resume_merge_in_progress() {
+    let dir = tempdir().unwrap();
+    let repo = setup_conflict(dir.path());
+
+    // Leave the selector after starting to resolve
+    run_ok(&mut merge_answering(&repo, &[], "1\n"));
+    assert!(repo.join(".git/MERGE_HEAD").exists());
+    assert!(readme(&repo).contains("<<<<<<<"));
+
+    let output = run_ok(&mut merge_answering(&repo, &[], "1\n3\n1\n"));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("in the middle of a merge"));
+    assert_eq!(parent_count(&repo, "main"), 2);
+    assert_eq!(readme(&repo), "main\n");
+    assert!(!repo.join(".git/MERGE_HEAD").exists());
+}
@@ -0,0 +1,78 @@
+use assert_cmd::Command;
+use tempfile::tempdir;
+
+mod common;
+use common::{init_repo, run_ok, trees};
+
+#[test]
+fn test_add_prints_only_the_path_on_stdout() {
+    let dir = tempdir().unwrap();
+    let repo = init_repo(dir.path(), "repo");
+
+    let output = run_ok(&mut trees(&repo, &["add", "feature"]));
+
+    let expected = dir.path().join("repo-feature").canonicalize().unwrap();
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        format!("{}\n", expected.display())
+    );
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Branch 'feature' created"));
+}
+
+#[test]
+fn test_quiet_and_verbose() {
+    let dir = tempdir().unwrap();
+    let repo = init_repo(dir.path(), "repo");
+
+    let quiet = trees(&repo, &["add", "quiet", "--quiet"]).output().unwrap();
+    assert!(quiet.status.success());
+    assert!(quiet.stderr.is_empty());
+
+    let verbose = trees(&repo, &["add", "loud", "--verbose"])
+        .output()
+        .unwrap();
+    assert!(verbose.status.success());
+    assert!(String::from_utf8_lossy(&verbose.stderr).contains("Worktree directory"));
+
+    Command::cargo_bin("trees-bin")
+        .unwrap()
+        .args(["list", "--quiet", "--verbose"])
+        .assert()
+        .failure();
+}
+
+#[test]
+fn test_fallback_selector_keeps_menu_off_stdout() {
+    let dir = tempdir().unwrap();
+    let repo = init_repo(dir.path(), "repo");
+
+    // Options are the local branch "main" and "Create new branch"
+    let output = run_ok(
+        trees(&repo, &["add"])
+            .env("TERM", "dumb")
+            .write_stdin("2\nfrom-menu\n"),
+    );
+    let expected = dir.path().join("repo-from-menu").canonicalize().unwrap();
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        format!("{}\n", expected.display())
+    );
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Select an option:"));
+    assert!(stderr.contains("Enter new branch name:"));
+}
+
+#[test]
+fn test_pull_prints_only_its_summary_on_stdout() {
+    let dir = tempdir().unwrap();
+    let repo = init_repo(dir.path(), "repo");
+
+    let output = run_ok(&mut trees(&repo, &["pull"]));
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.len(), 2, "{stdout}");
+    assert!(lines[0].starts_with("NAME"));
+    assert!(lines[1].starts_with("main "));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Fetched all remote branches"));
+}
@@ -0,0 +1,55 @@
+use std::fs;
+use std::path::Path;
+use tempfile::tempdir;
+
+mod common;
+use common::{add_worktree, git, init_repo, run_ok, trees};
+
+/// Run trees-bin successfully with a PATH that has no git on it
+fn trees_without_git(repo: &Path, args: &[&str]) -> std::process::Output {
+    let empty = tempdir().unwrap();
+    run_ok(
+        trees(repo, &[])
+            .env("PATH", empty.path())
+            .env("TERM", "dumb")
+            .args(args),
+    )
+}
+
+#[test]
+fn test_rm_without_git_cli() {
+    let dir = tempdir().unwrap();
+    let repo = init_repo(dir.path(), "repo");
+    let one = add_worktree(&repo, "one");
+    let locked = add_worktree(&repo, "locked");
+    git(&repo, &["worktree", "lock", locked.to_str().unwrap()]);
+
+    trees_without_git(&repo, &["rm", "one", "locked", "--yes", "--unlock"]);
+    assert!(!one.exists());
+    assert!(!locked.exists());
+    assert!(!repo.join(".git/worktrees/one").exists());
+    assert!(!repo.join(".git/worktrees/locked").exists());
+    assert_eq!(git(&repo, &["worktree", "list"]).lines().count(), 1);
+}
+
+#[test]
+fn test_prune_missing_directories() {
+    let dir = tempdir().unwrap();
+    let repo = init_repo(dir.path(), "repo");
+    let gone = add_worktree(&repo, "gone");
+    let kept = add_worktree(&repo, "kept");
+    let locked = add_worktree(&repo, "locked");
+    git(&repo, &["worktree", "lock", locked.to_str().unwrap()]);
+    fs::remove_dir_all(&gone).unwrap();
+    fs::remove_dir_all(&locked).unwrap();
+
+    let output = trees_without_git(&repo, &["prune"]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Pruned worktree 'gone'"));
+    assert!(!repo.join(".git/worktrees/gone").exists());
+    // Locked entries are kept even though their directory is gone
+    assert!(repo.join(".git/worktrees/locked").exists());
+    assert!(kept.exists());
+
+    let output = trees_without_git(&repo, &["prune"]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Nothing to prune"));
+}
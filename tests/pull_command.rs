@@ -0,0 +1,343 @@
+use std::path::{Path, PathBuf};
+use tempfile::tempdir;
+
+mod common;
+use common::{commit_file, git, init_repo, result_for, run_ok, stdout_ok, trees};
+
+/// Run `trees pull` and return its per-worktree results from stdout
+fn trees_pull(repo: &Path) -> String {
+    trees_pull_with(repo, &[])
+}
+
+fn trees_pull_with(repo: &Path, args: &[&str]) -> String {
+    stdout_ok(&mut pull_command(repo, args))
+}
+
+/// Run `trees pull` expecting a worktree to conflict, which fails the command
+fn trees_pull_conflicting(repo: &Path, args: &[&str]) -> String {
+    let output = pull_command(repo, args).output().unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("failed to update or conflicted"));
+    String::from_utf8_lossy(&output.stdout).to_string()
+}
+
+fn pull_command(repo: &Path, args: &[&str]) -> assert_cmd::Command {
+    let mut command = trees(repo, &["pull"]);
+    command.env("LANG", "de_DE.UTF-8").args(args);
+    command
+}
+
+/// Add a worktree for `branch` tracking `origin/<branch>`
+fn add_tracking(repo: &Path, branch: &str) -> PathBuf {
+    let path = repo.parent().unwrap().join(branch);
+    git(
+        repo,
+        &[
+            "worktree",
+            "add",
+            "-q",
+            "--track",
+            "-b",
+            branch,
+            path.to_str().unwrap(),
+            &format!("origin/{branch}"),
+        ],
+    );
+    path
+}
+
+#[test]
+fn test_pull_reports_each_worktree() {
+    let dir = tempdir().unwrap();
+    let upstream = init_repo(dir.path(), "upstream");
+    for branch in ["current", "diverged", "dirty"] {
+        git(&upstream, &["branch", branch]);
+    }
+    git(
+        dir.path(),
+        &["clone", "-q", upstream.to_str().unwrap(), "repo"],
+    );
+    let repo = dir.path().join("repo");
+    let current = add_tracking(&repo, "current");
+    let diverged = add_tracking(&repo, "diverged");
+    let dirty = add_tracking(&repo, "dirty");
+    let local = dir.path().join("local");
+    git(
+        &repo,
+        &[
+            "worktree",
+            "add",
+            "-q",
+            "-b",
+            "local",
+            local.to_str().unwrap(),
+        ],
+    );
+
+    commit_file(&upstream, "new.txt", "new\n", "Upstream main");
+    git(&upstream, &["checkout", "-q", "diverged"]);
+    commit_file(&upstream, "theirs.txt", "theirs\n", "Upstream diverged");
+    git(&upstream, &["checkout", "-q", "dirty"]);
+    commit_file(&upstream, "theirs.txt", "theirs\n", "Upstream dirty");
+    git(&upstream, &["checkout", "-q", "main"]);
+    commit_file(&diverged, "ours.txt", "ours\n", "Local diverged");
+    std::fs::write(dirty.join("README.md"), "local edit\n").unwrap();
+
+    let diverged_head = git(&diverged, &["rev-parse", "HEAD"]);
+    let dirty_head = git(&dirty, &["rev-parse", "HEAD"]);
+
+    let output = trees_pull(&repo);
+    assert_eq!(result_for(&output, "main"), "fast-forwarded");
+    assert_eq!(result_for(&output, "current"), "up-to-date");
+    assert_eq!(result_for(&output, "diverged"), "diverged");
+    assert_eq!(result_for(&output, "dirty"), "skipped-dirty");
+    assert_eq!(result_for(&output, "local"), "no-upstream");
+
+    // main was fast-forwarded, including its working tree and index
+    assert_eq!(
+        git(&repo, &["rev-parse", "HEAD"]),
+        git(&upstream, &["rev-parse", "main"])
+    );
+    assert_eq!(
+        std::fs::read_to_string(repo.join("new.txt")).unwrap(),
+        "new\n"
+    );
+    assert_eq!(git(&repo, &["status", "--porcelain"]), "");
+
+    // Everything else was left alone
+    assert_eq!(git(&diverged, &["rev-parse", "HEAD"]), diverged_head);
+    assert_eq!(git(&dirty, &["rev-parse", "HEAD"]), dirty_head);
+    assert_eq!(
+        std::fs::read_to_string(dirty.join("README.md")).unwrap(),
+        "local edit\n"
+    );
+    assert!(!current.join("new.txt").exists());
+}
+
+#[test]
+fn test_pull_fast_forwards_linked_worktree() {
+    let dir = tempdir().unwrap();
+    let upstream = init_repo(dir.path(), "upstream");
+    git(&upstream, &["branch", "feature"]);
+    git(
+        dir.path(),
+        &["clone", "-q", upstream.to_str().unwrap(), "repo"],
+    );
+    let repo = dir.path().join("repo");
+    let feature = add_tracking(&repo, "feature");
+
+    git(&upstream, &["checkout", "-q", "feature"]);
+    commit_file(&upstream, "feature.txt", "feature\n", "Feature work");
+    git(&upstream, &["checkout", "-q", "main"]);
+
+    let output = trees_pull(&repo);
+    assert_eq!(result_for(&output, "feature"), "fast-forwarded");
+    assert_eq!(result_for(&output, "main"), "up-to-date");
+    assert_eq!(
+        git(&feature, &["rev-parse", "HEAD"]),
+        git(&upstream, &["rev-parse", "feature"])
+    );
+    assert!(feature.join("feature.txt").exists());
+    assert_eq!(git(&feature, &["status", "--porcelain"]), "");
+    // The main checkout is untouched
+    assert!(!repo.join("feature.txt").exists());
+}
+
+/// A clone of `upstream` whose `main` worktree is behind `origin/main`, with a
+/// committer identity for rebasing and stashing
+fn behind_clone(parent: &Path) -> (PathBuf, PathBuf) {
+    let upstream = init_repo(parent, "upstream");
+    git(parent, &["clone", "-q", upstream.to_str().unwrap(), "repo"]);
+    let repo = parent.join("repo");
+    git(&repo, &["config", "user.name", "Test User"]);
+    git(&repo, &["config", "user.email", "test@example.com"]);
+    commit_file(&upstream, "theirs.txt", "theirs\n", "Upstream work");
+    (upstream, repo)
+}
+
+#[test]
+fn test_pull_rebase_diverged_branch() {
+    let dir = tempdir().unwrap();
+    let (upstream, repo) = behind_clone(dir.path());
+    commit_file(&repo, "ours.txt", "ours\n", "Local work");
+
+    assert_eq!(result_for(&trees_pull(&repo), "main"), "diverged");
+    let output = trees_pull_with(&repo, &["--rebase"]);
+    assert_eq!(result_for(&output, "main"), "rebased");
+
+    assert_eq!(
+        git(&repo, &["rev-parse", "HEAD~1"]),
+        git(&upstream, &["rev-parse", "main"])
+    );
+    assert_eq!(git(&repo, &["log", "-1", "--format=%s"]), "Local work");
+    assert!(repo.join("theirs.txt").exists());
+    assert!(repo.join("ours.txt").exists());
+    assert_eq!(git(&repo, &["status", "--porcelain"]), "");
+}
+
+#[test]
+fn test_pull_rebase_conflict_leaves_branch_alone() {
+    let dir = tempdir().unwrap();
+    let (_upstream, repo) = behind_clone(dir.path());
+    commit_file(&repo, "theirs.txt", "ours\n", "Conflicting work");
+    let head = git(&repo, &["rev-parse", "HEAD"]);
+
+    // The summary is data, so --quiet keeps it
+    let output = trees_pull_conflicting(&repo, &["--rebase", "--quiet"]);
+    assert_eq!(result_for(&output, "main"), "conflict");
+    assert_eq!(git(&repo, &["rev-parse", "HEAD"]), head);
+    assert_eq!(
+        std::fs::read_to_string(repo.join("theirs.txt")).unwrap(),
+        "ours\n"
+    );
+    assert_eq!(git(&repo, &["status", "--porcelain"]), "");
+}
+
+#[test]
+fn test_pull_autostash_keeps_changes() {
+    let dir = tempdir().unwrap();
+    let (upstream, repo) = behind_clone(dir.path());
+    std::fs::write(repo.join("staged.txt"), "staged\n").unwrap();
+    git(&repo, &["add", "staged.txt"]);
+    std::fs::write(repo.join("README.md"), "edited\n").unwrap();
+    std::fs::write(repo.join("untracked.txt"), "untracked\n").unwrap();
+    let status = git(&repo, &["status", "--porcelain"]);
+
+    let output = trees_pull_with(&repo, &["--autostash"]);
+    assert_eq!(result_for(&output, "main"), "fast-forwarded");
+    assert_eq!(
+        git(&repo, &["rev-parse", "HEAD"]),
+        git(&upstream, &["rev-parse", "main"])
+    );
+    assert_eq!(git(&repo, &["status", "--porcelain"]), status);
+    assert_eq!(
+        std::fs::read_to_string(repo.join("README.md")).unwrap(),
+        "edited\n"
+    );
+    assert_eq!(git(&repo, &["stash", "list"]), "");
+}
+
+#[test]
+fn test_pull_autostash_conflict_restores_worktree() {
+    let dir = tempdir().unwrap();
+    let (upstream, repo) = behind_clone(dir.path());
+    commit_file(&upstream, "README.md", "upstream\n", "Upstream README");
+    std::fs::write(repo.join("staged.txt"), "staged\n").unwrap();
+    git(&repo, &["add", "staged.txt"]);
+    std::fs::write(repo.join("README.md"), "local\n").unwrap();
+    std::fs::write(repo.join("untracked.txt"), "untracked\n").unwrap();
+    let head = git(&repo, &["rev-parse", "HEAD"]);
+    let status = git(&repo, &["status", "--porcelain"]);
+    let staged = git(&repo, &["diff", "--cached"]);
+    let unstaged = git(&repo, &["diff"]);
+
+    let output = trees_pull_conflicting(&repo, &["--autostash"]);
+    assert_eq!(result_for(&output, "main"), "conflict");
+    assert_eq!(git(&repo, &["rev-parse", "HEAD"]), head);
+    assert_eq!(git(&repo, &["status", "--porcelain"]), status);
+    assert_eq!(git(&repo, &["diff", "--cached"]), staged);
+    assert_eq!(git(&repo, &["diff"]), unstaged);
+    assert_eq!(git(&repo, &["stash", "list"]), "");
+}
+
+#[test]
+fn test_pull_jobs_across_remotes_and_worktrees() {
+    let dir = tempdir().unwrap();
+    let upstream = init_repo(dir.path(), "upstream");
+    let other = init_repo(dir.path(), "other");
+    for branch in ["one", "two", "three"] {
+        git(&upstream, &["branch", branch]);
+    }
+    git(
+        dir.path(),
+        &["clone", "-q", upstream.to_str().unwrap(), "repo"],
+    );
+    let repo = dir.path().join("repo");
+    git(&repo, &["remote", "add", "other", other.to_str().unwrap()]);
+    git(&repo, &["fetch", "-q", "other"]);
+    let paths: Vec<PathBuf> = ["one", "two", "three"]
+        .iter()
+        .map(|branch| add_tracking(&repo, branch))
+        .collect();
+    let other_main = dir.path().join("other-main");
+    git(
+        &repo,
+        &[
+            "worktree",
+            "add",
+            "-q",
+            "--track",
+            "-b",
+            "other-main",
+            other_main.to_str().unwrap(),
+            "other/main",
+        ],
+    );
+
+    for branch in ["one", "two", "three"] {
+        git(&upstream, &["checkout", "-q", branch]);
+        commit_file(&upstream, "new.txt", branch, "Upstream work");
+    }
+    git(&upstream, &["checkout", "-q", "main"]);
+    commit_file(&other, "other.txt", "other\n", "Other work");
+
+    let output = run_ok(&mut pull_command(&repo, &["--jobs", "2"]));
+    assert!(
+        !output.stderr.contains(&b'\x1b'),
+        "progress drawn without a terminal"
+    );
+    let output = String::from_utf8_lossy(&output.stdout);
+    for name in ["one", "two", "three", "other-main"] {
+        assert_eq!(result_for(&output, name), "fast-forwarded");
+    }
+    assert_eq!(result_for(&output, "main"), "up-to-date");
+    for (path, branch) in paths.iter().zip(["one", "two", "three"]) {
+        assert_eq!(
+            std::fs::read_to_string(path.join("new.txt")).unwrap(),
+            branch
+        );
+    }
+    assert!(other_main.join("other.txt").exists());
+}
+
+#[test]
+fn test_pull_rejects_zero_jobs() {
+    let dir = tempdir().unwrap();
+    let repo = init_repo(dir.path(), "repo");
+    trees(&repo, &["pull", "--jobs", "0"]).assert().failure();
+}
+
+#[test]
+fn test_pull_reports_failed_fetch() {
+    let dir = tempdir().unwrap();
+    let repo = init_repo(dir.path(), "repo");
+    let missing = dir.path().join("missing");
+    git(&repo, &["remote", "add", "gone", missing.to_str().unwrap()]);
+
+    let output = trees(&repo, &["pull"]).output().unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("Failed to fetch from remote 'gone'"),
+        "unexpected error: {stderr}"
+    );
+}
+
+#[test]
+fn test_pull_respects_url_instead_of() {
+    let dir = tempdir().unwrap();
+    let (upstream, repo) = behind_clone(dir.path());
+    let base = format!("file://{}/", dir.path().canonicalize().unwrap().display());
+    git(
+        &repo,
+        &["config", &format!("url.{base}.insteadOf"), "mirror:"],
+    );
+    git(&repo, &["remote", "set-url", "origin", "mirror:upstream"]);
+
+    let output = trees_pull(&repo);
+    assert_eq!(result_for(&output, "main"), "fast-forwarded");
+    assert_eq!(
+        git(&repo, &["rev-parse", "HEAD"]),
+        git(&upstream, &["rev-parse", "main"])
+    );
+}
@@ -0,0 +1,296 @@
+use std::path::{Path, PathBuf};
+use tempfile::tempdir;
+
+mod common;
+use common::{commit_file, git, init_repo, run_ok, trees};
+
+fn rm_command(repo: &Path, args: &[&str], stdin: &str) -> assert_cmd::Command {
+    let mut command = trees(repo, &["rm"]);
+    command.env("TERM", "dumb").args(args).write_stdin(stdin);
+    command
+}
+
+/// A clone with linked worktrees in `<parent>/<name>` on branch `<name>`
+fn setup(parent: &Path, names: &[&str]) -> (PathBuf, Vec<PathBuf>) {
+    let upstream = init_repo(parent, "upstream");
+    git(parent, &["clone", "-q", upstream.to_str().unwrap(), "repo"]);
+    let repo = parent.join("repo");
+    let paths = names
+        .iter()
+        .map(|name| {
+            let path = parent.join(name);
+            git(
+                &repo,
+                &["worktree", "add", "-q", "-b", name, path.to_str().unwrap()],
+            );
+            path
+        })
+        .collect();
+    (repo, paths)
+}
+
+#[test]
+fn test_rm_by_name_branch_and_path() {
+    let dir = tempdir().unwrap();
+    let (repo, paths) = setup(dir.path(), &["one", "two"]);
+    let three = dir.path().join("three-dir");
+    git(
+        &repo,
+        &[
+            "worktree",
+            "add",
+            "-q",
+            "-b",
+            "three",
+            three.to_str().unwrap(),
+        ],
+    );
+
+    run_ok(&mut rm_command(
+        &repo,
+        &["one", "three", paths[1].to_str().unwrap(), "--yes"],
+        "",
+    ));
+    assert!(!paths[0].exists());
+    assert!(!paths[1].exists());
+    assert!(!three.exists());
+    assert_eq!(git(&repo, &["worktree", "list"]).lines().count(), 1);
+}
+
+#[test]
+fn test_rm_asks_without_yes() {
+    let dir = tempdir().unwrap();
+    let (repo, paths) = setup(dir.path(), &["one"]);
+
+    run_ok(&mut rm_command(&repo, &["one"], "n\n"));
+    assert!(paths[0].exists());
+
+    run_ok(&mut rm_command(&repo, &["one"], "y\n"));
+    assert!(!paths[0].exists());
+}
+
+#[test]
+fn test_rm_needs_force_for_changes_and_unpushed_commits() {
+    let dir = tempdir().unwrap();
+    let (repo, paths) = setup(dir.path(), &["clean", "dirty", "ahead"]);
+    std::fs::write(paths[1].join("new.txt"), "new\n").unwrap();
+    commit_file(&paths[2], "ahead.txt", "ahead\n", "Not pushed");
+
+    // One refusal stops the whole removal
+    let output = rm_command(&repo, &["clean", "dirty", "ahead", "--yes"], "")
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("'dirty' has uncommitted changes (use --force)"));
+    assert!(stderr.contains("'ahead' has 1 unpushed commit (use --force)"));
+    assert!(!stderr.contains("'clean'"));
+    assert!(paths.iter().all(|path| path.exists()));
+
+    run_ok(&mut rm_command(
+        &repo,
+        &["dirty", "ahead", "--force", "--yes"],
+        "",
+    ));
+    assert!(!paths[1].exists());
+    assert!(!paths[2].exists());
+    // The branch and its commit are kept
+    git(&repo, &["rev-parse", "--verify", "-q", "ahead"]);
+}
+
+#[test]
+fn test_rm_refuses_locked_and_main() {
+    let dir = tempdir().unwrap();
+    let (repo, paths) = setup(dir.path(), &["locked"]);
+    git(
+        &repo,
+        &["worktree", "lock", "--reason", "in use", "../locked"],
+    );
+
+    let output = rm_command(&repo, &["locked", "--force", "--yes"], "")
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("'locked' is locked: in use"));
+    assert!(paths[0].exists());
+
+    run_ok(&mut rm_command(&repo, &["locked", "--unlock", "--yes"], ""));
+    assert!(!paths[0].exists());
+
+    let output = rm_command(&repo, &["main", "--force", "--yes"], "")
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("main worktree"));
+    assert!(repo.exists());
+
+    let output = rm_command(&repo, &["nope", "--yes"], "").output().unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("No worktree matches 'nope'"));
+}
+
+fn branch_exists(repo: &Path, branch: &str) -> bool {
+    std::process::Command::new("git")
+        .args(["rev-parse", "--verify", "-q", branch])
+        .current_dir(repo)
+        .output()
+        .unwrap()
+        .status
+        .success()
+}
+
+#[test]
+fn test_rm_delete_branch_only_when_merged() {
+    let dir = tempdir().unwrap();
+    let (repo, paths) = setup(dir.path(), &["done", "wip"]);
+    commit_file(&paths[1], "wip.txt", "wip\n", "Local only");
+
+    let output = rm_command(&repo, &["done", "wip", "--delete-branch", "--yes"], "")
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("'wip' has 1 unpushed commit and unmerged branch 'wip' (use --force)"),
+        "unexpected error: {stderr}"
+    );
+    assert!(paths[0].exists());
+
+    run_ok(&mut rm_command(
+        &repo,
+        &["done", "--delete-branch", "--yes"],
+        "",
+    ));
+    assert!(!paths[0].exists());
+    assert!(!branch_exists(&repo, "refs/heads/done"));
+
+    run_ok(&mut rm_command(
+        &repo,
+        &["wip", "--delete-branch", "--force", "--yes"],
+        "",
+    ));
+    assert!(!branch_exists(&repo, "refs/heads/wip"));
+}
+
+#[test]
+fn test_rm_delete_branch_config_default() {
+    let dir = tempdir().unwrap();
+    let (repo, paths) = setup(dir.path(), &["one", "two"]);
+    git(&repo, &["config", "trees.deleteBranch", "true"]);
+
+    run_ok(&mut rm_command(
+        &repo,
+        &["one", "--keep-branch", "--yes"],
+        "",
+    ));
+    assert!(!paths[0].exists());
+    assert!(branch_exists(&repo, "refs/heads/one"));
+
+    run_ok(&mut rm_command(&repo, &["two", "--yes"], ""));
+    assert!(!paths[1].exists());
+    assert!(!branch_exists(&repo, "refs/heads/two"));
+}
+
+#[test]
+fn test_rm_delete_remote_branch() {
+    let dir = tempdir().unwrap();
+    let seed = init_repo(dir.path(), "seed");
+    git(
+        dir.path(),
+        &[
+            "clone",
+            "-q",
+            "--bare",
+            seed.to_str().unwrap(),
+            "remote.git",
+        ],
+    );
+    let remote = dir.path().join("remote.git");
+    git(
+        dir.path(),
+        &["clone", "-q", remote.to_str().unwrap(), "repo"],
+    );
+    let repo = dir.path().join("repo");
+    let topic = dir.path().join("topic");
+    git(
+        &repo,
+        &[
+            "worktree",
+            "add",
+            "-q",
+            "-b",
+            "topic",
+            topic.to_str().unwrap(),
+        ],
+    );
+    commit_file(&topic, "topic.txt", "topic\n", "Topic work");
+    git(&topic, &["push", "-q", "-u", "origin", "topic"]);
+    assert!(branch_exists(&remote, "refs/heads/topic"));
+
+    // Only its upstream has the commit, and that is deleted too
+    let output = rm_command(&repo, &["topic", "--delete-remote", "--yes"], "")
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("unmerged branch 'topic'"));
+    assert!(topic.exists());
+    assert!(branch_exists(&remote, "refs/heads/topic"));
+
+    run_ok(&mut rm_command(
+        &repo,
+        &["topic", "--delete-remote", "--force", "--yes"],
+        "",
+    ));
+    assert!(!topic.exists());
+    assert!(!branch_exists(&repo, "refs/heads/topic"));
+    assert!(!branch_exists(&repo, "refs/remotes/origin/topic"));
+    assert!(!branch_exists(&remote, "refs/heads/topic"));
+    assert!(branch_exists(&remote, "refs/heads/main"));
+}
+
+#[test]
+fn test_rm_delete_remote_skips_branch_tracking_another() {
+    let dir = tempdir().unwrap();
+    let seed = init_repo(dir.path(), "seed");
+    git(
+        dir.path(),
+        &[
+            "clone",
+            "-q",
+            "--bare",
+            seed.to_str().unwrap(),
+            "remote.git",
+        ],
+    );
+    let remote = dir.path().join("remote.git");
+    git(
+        dir.path(),
+        &["clone", "-q", remote.to_str().unwrap(), "repo"],
+    );
+    let repo = dir.path().join("repo");
+    // Started from origin/main, so it tracks main instead of a branch of its own
+    let feat = dir.path().join("feat");
+    git(
+        &repo,
+        &[
+            "worktree",
+            "add",
+            "-q",
+            "-b",
+            "feat",
+            feat.to_str().unwrap(),
+            "origin/main",
+        ],
+    );
+
+    let output = run_ok(&mut rm_command(
+        &repo,
+        &["feat", "--delete-remote", "--yes"],
+        "",
+    ));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("not deleting it"));
+    assert!(!feat.exists());
+    assert!(!branch_exists(&repo, "refs/heads/feat"));
+    assert!(branch_exists(&remote, "refs/heads/main"));
+    assert!(branch_exists(&repo, "refs/remotes/origin/main"));
+}
@@ -8,8 +8,11 @@ fn test_shell_script_handles_add_command() {
     let output = cmd.output().unwrap();
     let script = String::from_utf8_lossy(&output.stdout);
 
-    // Verify that add command is handled correctly
-    assert!(script.contains("add|rm|merge|pull|list"));
+    // Verify that add command is handled correctly: it prints the new
+    // worktree directory, so it must not be in the run-directly list
+    assert!(script.contains("rm|merge|pull|list"));
+    assert!(!script.contains("add|rm"));
+    assert!(script.contains("DIR=$(trees-bin \"$@\" --dir-only)"));
     assert!(script.contains("trees-bin \"$@\""));
     assert!(script.contains("return $STATUS"));
 }
@@ -32,8 +35,11 @@ fn test_bash_script_handles_add_command() {
     let output = cmd.output().unwrap();
     let script = String::from_utf8_lossy(&output.stdout);
 
-    // Verify that add command is handled correctly
-    assert!(script.contains("add|rm|merge|pull|list"));
+    // Verify that add command is handled correctly: it prints the new
+    // worktree directory, so it must not be in the run-directly list
+    assert!(script.contains("rm|merge|pull|list"));
+    assert!(!script.contains("add|rm"));
+    assert!(script.contains("DIR=$(trees-bin \"$@\" --dir-only)"));
     assert!(script.contains("trees-bin \"$@\""));
     assert!(script.contains("return $STATUS"));
 }
@@ -46,7 +52,9 @@ fn test_fish_script_handles_add_command() {
     let script = String::from_utf8_lossy(&output.stdout);
 
     // Verify that add command is handled correctly
-    assert!(script.contains("add\" \"rm\" \"merge\" \"pull\" \"list"));
+    assert!(script.contains("\"rm\" \"merge\" \"pull\" \"list"));
+    assert!(!script.contains("\"add\" \"rm\""));
+    assert!(script.contains("set DIR (trees-bin $argv --dir-only)"));
     assert!(script.contains("trees-bin $argv"));
     assert!(script.contains("return $STATUS"));
 }
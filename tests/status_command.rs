@@ -0,0 +1,135 @@
+use std::path::Path;
+use tempfile::tempdir;
+
+mod common;
+use common::{commit_file, git, init_repo, stdout_ok, trees};
+
+fn trees_status(path: &Path) -> String {
+    stdout_ok(&mut trees(path, &["status"]))
+}
+
+/// Split the row for worktree `name` into its columns; the last commit age
+/// spans several words and is kept together
+fn row(output: &str, name: &str) -> Vec<String> {
+    let line = output
+        .lines()
+        .find(|line| line.split_whitespace().next() == Some(name))
+        .unwrap_or_else(|| panic!("no row for {name} in:\n{output}"));
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let mut columns: Vec<String> = words[..11].iter().map(|w| w.to_string()).collect();
+    columns.push(words[11..].join(" "));
+    columns
+}
+
+#[test]
+fn test_status_counts_and_upstream() {
+    let dir = tempdir().unwrap();
+    let upstream = init_repo(dir.path(), "upstream");
+    git(
+        dir.path(),
+        &["clone", "-q", upstream.to_str().unwrap(), "repo"],
+    );
+    let repo = dir.path().join("repo");
+    commit_file(&upstream, "theirs.txt", "theirs\n", "Upstream work");
+    git(&repo, &["fetch", "-q"]);
+    commit_file(&repo, "ours.txt", "ours\n", "Local work");
+
+    std::fs::write(repo.join("staged.txt"), "staged\n").unwrap();
+    git(&repo, &["add", "staged.txt"]);
+    std::fs::write(repo.join("README.md"), "changed\n").unwrap();
+    std::fs::write(repo.join("ours.txt"), "changed\n").unwrap();
+    std::fs::write(repo.join("untracked.txt"), "new\n").unwrap();
+
+    let output = trees_status(&repo);
+    assert!(output.starts_with("NAME"), "missing header:\n{output}");
+    let main = row(&output, "main");
+    let head = git(&repo, &["rev-parse", "--short=8", "HEAD"]);
+    assert_eq!(
+        main,
+        [
+            "main",
+            "main",
+            head.as_str(),
+            "origin/main",
+            "1",
+            "1",
+            "1",
+            "2",
+            "1",
+            "0",
+            "-",
+            "just now"
+        ]
+    );
+}
+
+#[test]
+fn test_status_stash_and_operation_per_worktree() {
+    let dir = tempdir().unwrap();
+    let repo = init_repo(dir.path(), "repo");
+    let feature = dir.path().join("feature");
+    git(
+        &repo,
+        &[
+            "worktree",
+            "add",
+            "-q",
+            "-b",
+            "feature",
+            feature.to_str().unwrap(),
+        ],
+    );
+
+    // A stash made on feature is only counted for feature
+    std::fs::write(feature.join("README.md"), "stashed\n").unwrap();
+    git(&feature, &["stash", "-q"]);
+
+    // Conflicting changes leave main in the middle of a merge
+    commit_file(&feature, "conflict.txt", "feature\n", "Feature side");
+    commit_file(&repo, "conflict.txt", "main\n", "Main side");
+    let merge = std::process::Command::new("git")
+        .args(["merge", "feature"])
+        .current_dir(&repo)
+        .env("GIT_AUTHOR_NAME", "Test User")
+        .env("GIT_AUTHOR_EMAIL", "test@example.com")
+        .env("GIT_COMMITTER_NAME", "Test User")
+        .env("GIT_COMMITTER_EMAIL", "test@example.com")
+        .output()
+        .unwrap();
+    assert!(!merge.status.success(), "merge should conflict");
+
+    let output = trees_status(&repo);
+    let main = row(&output, "main");
+    let feature_row = row(&output, "feature");
+    assert_eq!(main[3], "-", "no upstream expected:\n{output}");
+    assert_eq!(main[9], "0");
+    assert_eq!(main[10], "merge");
+    assert_eq!(feature_row[9], "1");
+    assert_eq!(feature_row[10], "-");
+}
+
+#[test]
+fn test_status_missing_worktree() {
+    let dir = tempdir().unwrap();
+    let repo = init_repo(dir.path(), "repo");
+    let gone = dir.path().join("gone");
+    git(
+        &repo,
+        &[
+            "worktree",
+            "add",
+            "-q",
+            "-b",
+            "gone",
+            gone.to_str().unwrap(),
+        ],
+    );
+    std::fs::remove_dir_all(&gone).unwrap();
+
+    let output = trees_status(&repo);
+    let line = output
+        .lines()
+        .find(|line| line.starts_with("gone "))
+        .unwrap_or_else(|| panic!("no row for gone in:\n{output}"));
+    assert!(line.ends_with("(prunable)"), "unexpected row: {line}");
+}
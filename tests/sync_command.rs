@@ -0,0 +1,136 @@
+use std::path::{Path, PathBuf};
+use tempfile::tempdir;
+
+mod common;
+use common::{add_worktree, commit_file, git, init_repo, result_for, rev, trees};
+
+/// Run `trees sync`, which fails because a worktree conflicts, and return
+/// its per-worktree results from stdout
+fn trees_sync(repo: &Path, args: &[&str]) -> String {
+    let output = trees(repo, &["sync"]).args(args).output().unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("1 worktree(s) failed to update"));
+    String::from_utf8_lossy(&output.stdout).to_string()
+}
+
+/// A clone with feature worktrees in every situation, and a new commit on
+/// upstream main that changes README.md
+fn setup(parent: &Path) -> PathBuf {
+    let upstream = init_repo(parent, "upstream");
+    git(parent, &["clone", "-q", upstream.to_str().unwrap(), "repo"]);
+    let repo = parent.join("repo");
+    git(&repo, &["config", "user.name", "Test User"]);
+    git(&repo, &["config", "user.email", "test@example.com"]);
+
+    let ahead = add_worktree(&repo, "ahead");
+    commit_file(&ahead, "ahead.txt", "ahead\n", "Add ahead");
+    add_worktree(&repo, "behind");
+    let dirty = add_worktree(&repo, "dirty");
+    commit_file(&dirty, "dirty.txt", "dirty\n", "Add dirty");
+    std::fs::write(dirty.join("dirty.txt"), "changed\n").unwrap();
+    let conflict = add_worktree(&repo, "conflict");
+    commit_file(&conflict, "README.md", "conflict\n", "Change readme");
+
+    commit_file(
+        &upstream,
+        "README.md",
+        "upstream\n",
+        "Change readme upstream",
+    );
+    repo
+}
+
+#[test]
+fn test_sync_rebases_onto_default_branch() {
+    let dir = tempdir().unwrap();
+    let repo = setup(dir.path());
+    let main = rev(&repo, "main");
+
+    // The summary is data, so --quiet keeps it
+    let output = trees_sync(&repo, &["--quiet"]);
+    assert_eq!(result_for(&output, "ahead"), "rebased");
+    assert_eq!(result_for(&output, "behind"), "fast-forwarded");
+    // The default branch's worktree is pull's job
+    assert!(!output.lines().any(|line| line.starts_with("repo ")));
+    assert_eq!(rev(&repo, "main"), main);
+
+    let origin = rev(&repo, "origin/main");
+    assert_eq!(rev(&repo, "ahead^"), origin);
+    assert_eq!(rev(&repo, "behind"), origin);
+    let ahead = dir.path().join("ahead");
+    assert_eq!(
+        std::fs::read_to_string(ahead.join("README.md")).unwrap(),
+        "upstream\n"
+    );
+    assert_eq!(git(&ahead, &["status", "--porcelain"]), "");
+}
+
+#[test]
+fn test_sync_skips_dirty_worktree() {
+    let dir = tempdir().unwrap();
+    let repo = setup(dir.path());
+    let dirty_tip = rev(&repo, "dirty");
+
+    let output = trees_sync(&repo, &[]);
+    assert_eq!(result_for(&output, "dirty"), "skipped-dirty");
+    assert_eq!(rev(&repo, "dirty"), dirty_tip);
+    let dirty = dir.path().join("dirty");
+    assert_eq!(git(&dirty, &["status", "--porcelain"]), "M dirty.txt");
+    assert_eq!(
+        std::fs::read_to_string(dirty.join("dirty.txt")).unwrap(),
+        "changed\n"
+    );
+}
+
+#[test]
+fn test_sync_rolls_back_conflict() {
+    let dir = tempdir().unwrap();
+    let repo = setup(dir.path());
+    let conflict = dir.path().join("conflict");
+    let conflict_tip = rev(&repo, "conflict");
+    // Uncommitted changes are stashed first and must come back too
+    std::fs::write(conflict.join("local.txt"), "local\n").unwrap();
+    git(&conflict, &["add", "local.txt"]);
+
+    for args in [&["--autostash"][..], &["--merge", "--autostash"]] {
+        let output = trees_sync(&repo, args);
+        assert_eq!(result_for(&output, "conflict"), "conflict");
+        assert_eq!(rev(&repo, "conflict"), conflict_tip);
+        assert_eq!(git(&conflict, &["status", "--porcelain"]), "A  local.txt");
+        assert_eq!(
+            std::fs::read_to_string(conflict.join("README.md")).unwrap(),
+            "conflict\n"
+        );
+        assert_eq!(git(&conflict, &["stash", "list"]), "");
+        assert!(!repo.join(".git/worktrees/conflict/rebase-merge").exists());
+        assert!(!repo.join(".git/worktrees/conflict/MERGE_HEAD").exists());
+    }
+}
+
+#[test]
+fn test_sync_merge_with_autostash() {
+    let dir = tempdir().unwrap();
+    let repo = setup(dir.path());
+    let ahead_tip = rev(&repo, "ahead");
+
+    let output = trees_sync(&repo, &["--merge", "--autostash"]);
+    assert_eq!(result_for(&output, "ahead"), "merged");
+    assert_eq!(result_for(&output, "dirty"), "merged");
+    assert_eq!(result_for(&output, "conflict"), "conflict");
+
+    let origin = rev(&repo, "origin/main");
+    assert_eq!(rev(&repo, "ahead^1"), ahead_tip);
+    assert_eq!(rev(&repo, "ahead^2"), origin);
+    assert_eq!(
+        git(&repo, &["log", "-1", "--format=%s", "ahead"]),
+        "Merge remote-tracking branch 'origin/main' into ahead"
+    );
+    // The stashed change is back on top of the merge
+    let dirty = dir.path().join("dirty");
+    assert_eq!(rev(&repo, "dirty^2"), origin);
+    assert_eq!(
+        std::fs::read_to_string(dirty.join("dirty.txt")).unwrap(),
+        "changed\n"
+    );
+    assert_eq!(git(&dirty, &["stash", "list"]), "");
+}
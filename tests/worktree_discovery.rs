@@ -0,0 +1,92 @@
+use tempfile::tempdir;
+
+mod common;
+use common::{commit_file, git, init_repo, run_ok, stdout_ok, trees};
+
+fn trees_list(repo: &std::path::Path) -> String {
+    stdout_ok(&mut trees(repo, &["list"]))
+}
+
+#[test]
+fn test_list_uses_real_worktree_paths() {
+    let dir = tempdir().unwrap();
+    let repo = init_repo(dir.path(), "repo");
+    let elsewhere = dir.path().join("wt").join("feature");
+    git(
+        &repo,
+        &[
+            "worktree",
+            "add",
+            "-q",
+            "-b",
+            "feature",
+            elsewhere.to_str().unwrap(),
+        ],
+    );
+
+    let list = trees_list(&repo);
+    let canonical = elsewhere.canonicalize().unwrap();
+    assert!(
+        list.contains(&format!("{} ", canonical.display())),
+        "unexpected list output:\n{list}"
+    );
+    assert!(list.contains("[feature]"));
+}
+
+#[test]
+fn test_list_reports_worktree_states() {
+    let dir = tempdir().unwrap();
+    let repo = init_repo(dir.path(), "repo");
+    for name in ["locked", "gone", "kept"] {
+        let path = dir.path().join("wt").join(name);
+        git(
+            &repo,
+            &["worktree", "add", "-q", "-b", name, path.to_str().unwrap()],
+        );
+    }
+    git(
+        &repo,
+        &["worktree", "lock", "--reason", "usb", "../wt/locked"],
+    );
+    git(&repo, &["worktree", "lock", "../wt/kept"]);
+    std::fs::remove_dir_all(dir.path().join("wt").join("gone")).unwrap();
+    std::fs::remove_dir_all(dir.path().join("wt").join("kept")).unwrap();
+
+    let list = trees_list(&repo);
+    let line = |name: &str| {
+        list.lines()
+            .find(|line| line.contains(&format!("wt/{name} ")))
+            .unwrap_or_else(|| panic!("no line for {name} in:\n{list}"))
+            .to_string()
+    };
+    assert!(line("locked").ends_with(" locked"));
+    assert!(line("gone").ends_with(" prunable"));
+    assert!(line("kept").ends_with(" missing"));
+}
+
+#[test]
+fn test_merge_worktree_outside_sibling_directory() {
+    let dir = tempdir().unwrap();
+    let repo = init_repo(dir.path(), "repo");
+    let elsewhere = dir.path().join("wt").join("feature");
+    git(
+        &repo,
+        &[
+            "worktree",
+            "add",
+            "-q",
+            "-b",
+            "feature",
+            elsewhere.to_str().unwrap(),
+        ],
+    );
+    commit_file(&elsewhere, "feature.txt", "feature\n", "Add feature");
+
+    // Source is the second option (feature), target the first (main)
+    run_ok(
+        trees(&repo, &["merge"])
+            .env("TERM", "dumb")
+            .write_stdin("2\n1\n"),
+    );
+    assert!(repo.join("feature.txt").exists());
+}